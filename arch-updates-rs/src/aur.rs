@@ -0,0 +1,2228 @@
+//! AUR and devel package update checks, enabled by the `aur` feature.
+use crate::{
+    check_pacman_updates_offline, check_pacman_updates_online, parse_ver_and_rel, Error, Result,
+    Update, UpdateChecker,
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use core::str;
+use futures::{channel::mpsc, stream::FuturesOrdered, StreamExt, TryStreamExt};
+use srcinfo::Srcinfo;
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::process::Command;
+use version_compare::Version;
+
+/// Packages ending with one of the devel suffixes will be checked against the
+/// repository, as well as just the pkgver and pkgrel.
+pub const DEVEL_SUFFIXES: [&str; 1] = ["-git"];
+
+/// Current status of an installed AUR package, vs the status of the latest
+/// version.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AurUpdate {
+    pub pkgname: String,
+    pub pkgver_cur: String,
+    pub pkgrel_cur: String,
+    pub pkgver_new: String,
+    pub pkgrel_new: String,
+    /// If the package has been flagged out-of-date on the AUR, the time it was
+    /// flagged.
+    pub flagged_out_of_date: Option<DateTime<Utc>>,
+    /// AUR pkgbase the package is built from. This differs from `pkgname` for
+    /// split packages, and is what AUR package pages are named after. `None`
+    /// if unknown, e.g the package wasn't in the cache for an offline check.
+    pub pkgbase: Option<String>,
+}
+
+impl AurUpdate {
+    /// True if only the pkgrel has changed, i.e the package has been rebuilt
+    /// without a new upstream version.
+    pub fn is_pkgrel_only(&self) -> bool {
+        self.pkgver_cur == self.pkgver_new
+    }
+}
+
+/// Current status of an installed devel package, vs latest commit hash on the
+/// source repo.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DevelUpdate {
+    pub pkgname: String,
+    pub pkgver_cur: String,
+    pub pkgrel_cur: String,
+    /// When checking a devel update, we don't get a pkgver/pkgrel so-to-speak,
+    /// we instead get the github ref.
+    pub ref_id_new: String,
+    /// Full commit hash of the latest commit on the source repo, for consumers
+    /// that want to do their own comparison. `ref_id_new` is a prefix of this.
+    pub ref_id_full: String,
+    /// Date of the latest commit on the source repo. Only populated by
+    /// `check_devel_updates_online_with_dates()` as it requires an extra round
+    /// trip.
+    pub remote_commit_date: Option<DateTime<FixedOffset>>,
+    /// When the source repo was checked for `ref_id_new`. Offline checks keep
+    /// the time from the online check that created the cache.
+    pub checked_at: DateTime<Utc>,
+    /// AUR pkgbase the package is built from. This differs from `pkgname` for
+    /// split packages, and is what AUR package pages are named after.
+    pub pkgbase: Option<String>,
+}
+
+/// Results of checking all update sources together. Each source is checked
+/// independently, so a failure in one source doesn't discard the results of
+/// the others.
+///
+/// The `Display` implementation gives a one line summary for logging, e.g
+/// `pacman: 4, aur: 1, devel: 0 (1 error)`. A source that failed is counted
+/// as 0 updates.
+#[derive(Debug)]
+pub struct AllUpdates {
+    pub pacman: Result<Vec<Update>>,
+    pub aur: Result<Vec<AurUpdate>>,
+    pub devel: Result<Vec<DevelUpdate>>,
+    /// Problems that didn't fail a source, but meant some packages couldn't be
+    /// fully checked.
+    pub warnings: Vec<Warning>,
+}
+
+impl AllUpdates {
+    /// Remove pacman and AUR updates where only the pkgrel has changed, i.e
+    /// rebuilds without a new upstream version. Sources that failed are left
+    /// as is.
+    ///
+    /// `UpdateChecker` does this itself if
+    /// `UpdateCheckerOptions::ignore_pkgrel_only` is set.
+    pub fn remove_pkgrel_only(&mut self) {
+        if let Ok(pacman) = &mut self.pacman {
+            pacman.retain(|update| !update.is_pkgrel_only());
+        }
+        if let Ok(aur) = &mut self.aur {
+            aur.retain(|update| !update.is_pkgrel_only());
+        }
+    }
+    /// Number of sources that failed to be checked.
+    pub fn error_count(&self) -> usize {
+        [self.pacman.is_err(), self.aur.is_err(), self.devel.is_err()]
+            .into_iter()
+            .filter(|is_err| *is_err)
+            .count()
+    }
+}
+
+impl std::fmt::Display for AllUpdates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn count<T>(updates: &Result<Vec<T>>) -> usize {
+            updates.as_ref().map_or(0, Vec::len)
+        }
+        write!(
+            f,
+            "pacman: {}, aur: {}, devel: {}",
+            count(&self.pacman),
+            count(&self.aur),
+            count(&self.devel)
+        )?;
+        match self.error_count() {
+            0 => Ok(()),
+            1 => write!(f, " (1 error)"),
+            n => write!(f, " ({n} errors)"),
+        }
+    }
+}
+
+/// Latest version of all aur and devel packages from
+/// `check_all_updates_online()` - for offline use.
+/// A cache will be `None` if the online check for that source failed, or was
+/// skipped as `UpdateCheckerOptions::offline_only` is set.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AllUpdatesCache {
+    pub aur: Option<Vec<AurUpdate>>,
+    pub devel: Option<Vec<DevelUpdate>>,
+    /// Installed AUR packages that are no longer on the AUR, as found by the
+    /// AUR check. This saves a separate `check_orphaned_aur_packages()` call.
+    /// `None` under the same conditions as `aur`.
+    pub orphaned: Option<Vec<Package>>,
+}
+
+impl AllUpdatesCache {
+    /// Compare this cache with a newer one, to find which aur and devel
+    /// packages have had an update appear or be resolved in between.
+    /// # Usage
+    /// ```no_run
+    /// # use arch_updates_rs::*;
+    /// # async {
+    /// let (_, old_cache) = check_all_updates_online().await;
+    /// let (_, new_cache) = check_all_updates_online().await;
+    /// let diff = old_cache.diff(&new_cache);
+    /// println!("New updates since last check: {:?}", diff.added);
+    /// # };
+    pub fn diff(&self, newer: &Self) -> UpdateDiff {
+        let old = self.due_pkgnames();
+        let new = newer.due_pkgnames();
+        UpdateDiff {
+            added: new.difference(&old).map(ToString::to_string).collect(),
+            removed: old.difference(&new).map(ToString::to_string).collect(),
+        }
+    }
+    fn due_pkgnames(&self) -> BTreeSet<&str> {
+        let aur = self
+            .aur
+            .iter()
+            .flatten()
+            .filter(|update| aur_update_due(update))
+            .map(|update| update.pkgname.as_str());
+        let devel = self
+            .devel
+            .iter()
+            .flatten()
+            .filter(|update| devel_update_due(update))
+            .map(|update| update.pkgname.as_str());
+        aur.chain(devel).collect()
+    }
+}
+
+/// Difference between the updates due in two `AllUpdatesCache`s, from
+/// `AllUpdatesCache::diff()`. Package names are sorted and deduplicated.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UpdateDiff {
+    /// Packages with an update due in the newer cache only.
+    pub added: Vec<String>,
+    /// Packages with an update due in the older cache only (e.g they have since
+    /// been updated).
+    pub removed: Vec<String>,
+}
+
+/// How long each source took to check, from
+/// `check_all_updates_online_with_timings()`. The sources are checked
+/// concurrently, so these overlap rather than add up to the total time.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CheckTimings {
+    /// Running `checkupdates`.
+    pub pacman: Duration,
+    /// Getting the installed AUR packages and querying the AUR.
+    pub aur: Duration,
+    /// Getting the installed devel packages, fetching their .SRCINFO files and
+    /// checking their source repositories.
+    pub devel: Duration,
+}
+
+/// A problem found during an online check that didn't fail the check, but
+/// meant a package couldn't be fully checked, from `AllUpdates::warnings`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Warning {
+    /// The devel package is no longer on the AUR, so its source repositories
+    /// can't be found.
+    DevelNotInAur(String),
+    /// The .SRCINFO for the pkgbase has no git sources that can be checked,
+    /// e.g because they are pinned to a commit or tag.
+    NoGitSources(String),
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DevelNotInAur(pkgname) => {
+                write!(f, "Devel package `{pkgname}` is no longer on the AUR")
+            }
+            Warning::NoGitSources(pkgbase) => {
+                write!(f, "No git sources found to check for `{pkgbase}`")
+            }
+        }
+    }
+}
+
+/// Stage of an online update check, reported by the `_with_progress` functions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Progress {
+    QueryingPacman,
+    QueryingAur,
+    /// Fetching the .SRCINFO of devel package `done + 1` of `total`.
+    FetchingSrcinfo {
+        done: usize,
+        total: usize,
+    },
+    /// Checking the source repositories of devel package `done + 1` of `total`.
+    CheckingGitRefs {
+        done: usize,
+        total: usize,
+    },
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Progress::QueryingPacman => write!(f, "Querying pacman"),
+            Progress::QueryingAur => write!(f, "Querying AUR"),
+            Progress::FetchingSrcinfo { done, total } => {
+                write!(f, "Fetching AUR srcinfo {}/{total}", done + 1)
+            }
+            Progress::CheckingGitRefs { done, total } => {
+                write!(f, "Checking git refs {}/{total}", done + 1)
+            }
+        }
+    }
+}
+
+/// Optional sender for `Progress` events. Reporting never blocks or fails the
+/// check - events are dropped if the receiver is full or has been dropped.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProgressSender(Option<mpsc::Sender<Progress>>);
+
+impl ProgressSender {
+    pub(crate) fn new(tx: Option<mpsc::Sender<Progress>>) -> Self {
+        Self(tx)
+    }
+    pub(crate) fn report(&self, progress: Progress) {
+        if let Some(tx) = &self.0 {
+            let _ = tx.clone().try_send(progress);
+        }
+    }
+}
+
+/// A locally installed foreign (AUR) package, as listed by `pacman -Qm`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Package {
+    pub pkgname: String,
+    pub pkgver: String,
+    pub pkgrel: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct PackageUrl<'a> {
+    remote: String,
+    protocol: &'a str,
+    branch: Option<&'a str>,
+}
+
+/// Check if any packages ending in `DEVEL_SUFFIXES` have updates to their
+/// source repositories.
+///
+/// Online version - this function checks the network.
+/// Returns a tuple of:
+///  - Packages that are not up to date.
+///  - Latest version of all devel packages - for offline use.
+///
+/// # Notes
+///  - For this to be accurate, it's reliant on each devel package having only
+///    one source URL. If this is not the case, the function will produce a
+///    DevelUpdate for each source url, and may assume one or more are out of
+///    date.
+///  - Split packages built from the same pkgbase share source repositories,
+///    so each pkgbase is only checked once, and the result is applied to each
+///    of its packages.
+///  - Packages that are no longer on the AUR are skipped. Use
+///    `check_orphaned_aur_packages()` to find these.
+///  - This is also reliant on VCS packages being good
+///    citizens and following the VCS Packaging Guidelines.
+///    <https://wiki.archlinux.org/title/VCS_package_guidelines>
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (updates, _) = check_devel_updates_online().await.unwrap();
+/// // Run `paru -Syu` in the terminal
+/// let (updates, _) = check_devel_updates_online().await.unwrap();
+/// assert!(updates.is_empty());
+/// # };
+pub async fn check_devel_updates_online() -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+    UpdateChecker::default().check_devel_updates_online().await
+}
+
+/// Check if any packages ending in `DEVEL_SUFFIXES` have updates to their
+/// source repositories, also getting the date of the latest commit on the
+/// source repositories.
+///
+/// As per `check_devel_updates_online()`, but `DevelUpdate::remote_commit_date`
+/// will be populated. Note that this requires an extra round trip (a shallow
+/// fetch) per source repository, so is slower.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (updates, _) = check_devel_updates_online_with_dates().await.unwrap();
+/// for update in updates {
+///     println!("{}: {:?}", update.pkgname, update.remote_commit_date);
+/// }
+/// # };
+pub async fn check_devel_updates_online_with_dates() -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)>
+{
+    UpdateChecker::default()
+        .check_devel_updates_online_with_dates()
+        .await
+}
+
+/// Check if any packages ending in `DEVEL_SUFFIXES` have updates to their
+/// source repositories, reporting progress as each package is checked.
+///
+/// As per `check_devel_updates_online()`, but a `Progress` event is sent to
+/// `progress` at each stage of the check. Events are dropped if the channel is
+/// full, so the check never waits on the receiver.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # use futures::{channel::mpsc, StreamExt};
+/// # async {
+/// let (tx, rx) = mpsc::channel(16);
+/// let print_progress = rx.for_each(|progress| async move { println!("{progress}") });
+/// let (updates, _) = futures::join!(
+///     check_devel_updates_online_with_progress(Some(tx)),
+///     print_progress
+/// );
+/// # };
+pub async fn check_devel_updates_online_with_progress(
+    progress: Option<mpsc::Sender<Progress>>,
+) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+    UpdateChecker::default()
+        .check_devel_updates_online_with_progress(progress)
+        .await
+}
+
+pub(crate) async fn check_devel_updates_online_impl(
+    backend: &impl AurBackend,
+    get_dates: bool,
+    progress: &ProgressSender,
+) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+    let devel_packages = filter_devel_packages(backend.aur_packages().await?);
+    let (updates, cache, _) =
+        check_devel_packages(backend, devel_packages, get_dates, progress).await?;
+    Ok((updates, cache))
+}
+
+/// Check the source repositories of `devel_packages` (already filtered by
+/// `filter_devel_packages()`).
+/// Returns a tuple of the updates due, all packages (the cache), and warnings
+/// for packages that couldn't be checked.
+async fn check_devel_packages(
+    backend: &impl AurBackend,
+    devel_packages: Vec<Package>,
+    get_dates: bool,
+    progress: &ProgressSender,
+) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>, Vec<Warning>)> {
+    // Packages that are no longer on the AUR can't be checked.
+    let (pkgbases, orphaned) = get_pkgbases(backend, devel_packages).await?;
+    let mut warnings = orphaned
+        .into_iter()
+        .map(|package| Warning::DevelNotInAur(package.pkgname))
+        .collect::<Vec<_>>();
+    let total = pkgbases.len();
+    let (devel_updates, no_sources): (Vec<_>, Vec<_>) =
+        futures::stream::iter(pkgbases.into_iter().enumerate())
+            .then(|(done, (pkgbase, packages))| async move {
+                progress.report(Progress::FetchingSrcinfo { done, total });
+                let srcinfo = backend.srcinfo(&pkgbase).await?;
+                progress.report(Progress::CheckingGitRefs { done, total });
+                let remotes = source_remotes(&srcinfo);
+                if remotes.is_empty() {
+                    return Ok((Vec::new(), Some(Warning::NoGitSources(pkgbase))));
+                }
+                let heads = remotes
+                    .into_iter()
+                    .map(|(remote, branch)| async move {
+                        // Failing to get the date shouldn't fail the whole check, since it's
+                        // supplementary information only.
+                        let remote_commit_date = match get_dates {
+                            true => backend
+                                .head_commit_date(&remote, branch.as_deref())
+                                .await
+                                .ok(),
+                            false => None,
+                        };
+                        let (ref_id_new, ref_id_full) =
+                            backend.head_identifier(remote, branch.as_deref()).await?;
+                        Ok::<_, Error>((ref_id_new, ref_id_full, remote_commit_date, Utc::now()))
+                    })
+                    .collect::<FuturesOrdered<_>>()
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                // Each package built from the pkgbase shares the same source repositories.
+                let pkgbase = &pkgbase;
+                let updates = packages
+                    .into_iter()
+                    .flat_map(|pkg| {
+                        heads.iter().map(
+                            move |(ref_id_new, ref_id_full, remote_commit_date, checked_at)| {
+                                DevelUpdate {
+                                    pkgname: pkg.pkgname.to_owned(),
+                                    pkgver_cur: pkg.pkgver.to_owned(),
+                                    pkgrel_cur: pkg.pkgrel.to_owned(),
+                                    ref_id_new: ref_id_new.to_owned(),
+                                    ref_id_full: ref_id_full.to_owned(),
+                                    remote_commit_date: *remote_commit_date,
+                                    checked_at: *checked_at,
+                                    pkgbase: Some(pkgbase.to_owned()),
+                                }
+                            },
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                Ok::<_, Error>((updates, None))
+            })
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter()
+            .unzip();
+    warnings.extend(no_sources.into_iter().flatten());
+    let devel_updates = dedup_devel_updates(devel_updates.into_iter().flatten().collect());
+    Ok((
+        devel_updates
+            .iter()
+            .filter(|update| devel_update_due(update))
+            .cloned()
+            .collect::<Vec<_>>(),
+        devel_updates,
+        warnings,
+    ))
+}
+
+/// Check if any packages ending in `DEVEL_SUFFIXES` have updates to their
+/// source repositories.
+///
+/// Offline version - this function doesn't use the network, but needs a
+/// reference to the latest version of all devel packages (returned from
+/// `check_devel_updates_online()`.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (online, cache) = check_devel_updates_online().await.unwrap();
+/// let offline = check_devel_updates_offline(&cache).await.unwrap();
+/// assert_eq!(online, offline);
+/// // Run `paru -Syu` in the terminal
+/// let offline = check_devel_updates_offline(&cache).await.unwrap();
+/// assert!(offline.is_empty());
+/// # };
+pub async fn check_devel_updates_offline(cache: &[DevelUpdate]) -> Result<Vec<DevelUpdate>> {
+    let devel_packages = get_devel_packages().await?;
+    let devel_updates = devel_packages
+        .iter()
+        .flat_map(|package| {
+            cache
+                .iter()
+                .filter(|cache_package| cache_package.pkgname == package.pkgname)
+                .map(move |cache_package| DevelUpdate {
+                    pkgname: package.pkgname.to_owned(),
+                    pkgver_cur: package.pkgver.to_owned(),
+                    pkgrel_cur: package.pkgrel.to_owned(),
+                    ref_id_new: cache_package.ref_id_new.to_owned(),
+                    ref_id_full: cache_package.ref_id_full.to_owned(),
+                    remote_commit_date: cache_package.remote_commit_date,
+                    checked_at: cache_package.checked_at,
+                    pkgbase: cache_package.pkgbase.to_owned(),
+                })
+        })
+        .filter(devel_update_due)
+        .collect();
+    Ok(dedup_devel_updates(devel_updates))
+}
+
+/// Check if any AUR packages have updates to their pkgver-pkgrel.
+///
+/// Online version - this function checks the network.
+/// Returns a tuple of:
+///  - Packages that are not up to date.
+///  - Latest version of all aur packages - for offline use.
+///
+/// # Notes
+///  - Locally installed packages that aren't in the AUR are currently not
+///    implemented and may return an error.
+///  - Devel packages (ending in `DEVEL_SUFFIXES`) are never returned as
+///    updates, they are checked by `check_devel_updates_online()` instead.
+///    They are still included in the cache.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (updates, _) = check_aur_updates_online().await.unwrap();
+/// // Run `paru -Syu` in the terminal
+/// let (updates, _) = check_aur_updates_online().await.unwrap();
+/// assert!(updates.is_empty());
+/// # };
+pub async fn check_aur_updates_online() -> Result<(Vec<AurUpdate>, Vec<AurUpdate>)> {
+    UpdateChecker::default().check_aur_updates_online().await
+}
+
+pub(crate) async fn check_aur_updates_online_impl(
+    backend: &impl AurBackend,
+) -> Result<(Vec<AurUpdate>, Vec<AurUpdate>)> {
+    let old = backend.aur_packages().await?;
+    let (updates, cache, _) = get_aur_updates(backend, old).await?;
+    Ok((updates, cache))
+}
+
+/// Get the latest version of each installed AUR package from the AUR.
+/// Returns a tuple of the updates due, all packages (the cache), and the
+/// packages that are no longer on the AUR.
+async fn get_aur_updates(
+    backend: &impl AurBackend,
+    old: Vec<Package>,
+) -> Result<(Vec<AurUpdate>, Vec<AurUpdate>, Vec<Package>)> {
+    // A system with no AUR packages is normal, so don't make an empty request
+    // to the AUR.
+    if old.is_empty() {
+        return Ok((Vec::new(), Vec::new(), Vec::new()));
+    }
+    let info = backend
+        .aur_info(
+            &old.iter()
+                .map(|pkg| pkg.pkgname.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .await?;
+    let orphaned = orphaned_packages(&old, &info);
+    let cache: Vec<AurUpdate> = info
+        .into_iter()
+        .filter_map(|new| {
+            let matching_old = &old.iter().find(|old| old.pkgname == new.name)?.clone();
+            let (pkgver_new, pkgrel_new) = parse_ver_and_rel_or_default(&new.version);
+            Some(AurUpdate {
+                pkgname: matching_old.pkgname.to_owned(),
+                pkgver_cur: matching_old.pkgver.to_owned(),
+                pkgrel_cur: matching_old.pkgrel.to_owned(),
+                pkgver_new,
+                pkgrel_new,
+                flagged_out_of_date: new
+                    .out_of_date
+                    .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+                pkgbase: Some(new.package_base),
+            })
+        })
+        .collect();
+    Ok((
+        cache
+            .iter()
+            .filter(|update| aur_update_due(update))
+            .cloned()
+            .collect(),
+        cache,
+        orphaned,
+    ))
+}
+
+/// Packages from `packages` that have no result in `info`, i.e are no longer
+/// on the AUR.
+fn orphaned_packages(packages: &[Package], info: &[raur::Package]) -> Vec<Package> {
+    packages
+        .iter()
+        .filter(|package| !info.iter().any(|info| info.name == package.pkgname))
+        .cloned()
+        .collect()
+}
+
+/// Check if any AUR packages have updates to their pkgver-pkgrel.
+///
+/// Offline version - this function doesn't use the network, but needs a
+/// reference to the latest version of all aur packages (returned from
+/// `check_aur_updates_online()`.
+///
+/// # Notes
+///  - Packages installed since the cache was created aren't in the cache, so
+///    are assumed to be up to date. Use `get_uncached_aur_packages()` to
+///    detect this, and run an online check instead.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (online, cache) = check_aur_updates_online().await.unwrap();
+/// let offline = check_aur_updates_offline(&cache).await.unwrap();
+/// assert_eq!(online, offline);
+/// // Run `paru -Syu` in the terminal
+/// let offline = check_aur_updates_offline(&cache).await.unwrap();
+/// assert!(offline.is_empty());
+/// # };
+pub async fn check_aur_updates_offline(cache: &[AurUpdate]) -> Result<Vec<AurUpdate>> {
+    let old = get_aur_packages().await?;
+    let updates = old
+        .iter()
+        .map(|old_package| {
+            let matching_cached = cache
+                .iter()
+                .find(|cache_package| cache_package.pkgname == old_package.pkgname);
+            let (pkgver_new, pkgrel_new, flagged_out_of_date, pkgbase) = match matching_cached {
+                Some(cache_package) => (
+                    cache_package.pkgver_new.to_owned(),
+                    cache_package.pkgrel_new.to_owned(),
+                    cache_package.flagged_out_of_date,
+                    cache_package.pkgbase.to_owned(),
+                ),
+                None => (
+                    old_package.pkgver.to_owned(),
+                    old_package.pkgrel.to_owned(),
+                    None,
+                    None,
+                ),
+            };
+            AurUpdate {
+                pkgname: old_package.pkgname.to_owned(),
+                pkgver_cur: old_package.pkgver.to_owned(),
+                pkgrel_cur: old_package.pkgrel.to_owned(),
+                pkgver_new,
+                pkgrel_new,
+                flagged_out_of_date,
+                pkgbase,
+            }
+        })
+        .filter(aur_update_due)
+        .collect();
+    Ok(updates)
+}
+
+/// Get a list of installed AUR packages that aren't in `cache`, e.g because
+/// they were installed after the online check that returned it. The offline
+/// checks can't check these, so if any are returned, an online check is
+/// needed. Devel packages are also AUR packages, so this applies to the devel
+/// cache as well.
+///
+/// Packages that aren't on the AUR are never in the cache, so will always be
+/// returned - these can be filtered out using `check_orphaned_aur_packages()`.
+///
+/// This function doesn't use the network.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (_, cache) = check_aur_updates_online().await.unwrap();
+/// // Install a new AUR package
+/// if !get_uncached_aur_packages(&cache).await.unwrap().is_empty() {
+///     let (_, cache) = check_aur_updates_online().await.unwrap();
+/// }
+/// # };
+pub async fn get_uncached_aur_packages(cache: &[AurUpdate]) -> Result<Vec<Package>> {
+    let installed = get_aur_packages().await?;
+    Ok(uncached_packages(installed, cache))
+}
+
+fn uncached_packages(installed: Vec<Package>, cache: &[AurUpdate]) -> Vec<Package> {
+    installed
+        .into_iter()
+        .filter(|package| {
+            !cache
+                .iter()
+                .any(|cache_package| cache_package.pkgname == package.pkgname)
+        })
+        .collect()
+}
+
+/// Get a list of installed AUR packages that are no longer on the AUR, e.g
+/// because they were deleted or renamed. These can't be checked for updates.
+///
+/// Online version - this function checks the network.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// for package in check_orphaned_aur_packages().await.unwrap() {
+///     println!("{} is no longer on the AUR", package.pkgname);
+/// }
+/// # };
+pub async fn check_orphaned_aur_packages() -> Result<Vec<Package>> {
+    UpdateChecker::default().check_orphaned_aur_packages().await
+}
+
+pub(crate) async fn check_orphaned_aur_packages_impl(
+    backend: &impl AurBackend,
+) -> Result<Vec<Package>> {
+    let (_, orphaned) = get_pkgbases(backend, backend.aur_packages().await?).await?;
+    Ok(orphaned)
+}
+
+/// Check if any pacman, AUR or devel packages have updates due.
+///
+/// Online version - this function checks the network.
+/// Returns a tuple of:
+///  - Results for each source. One source failing doesn't affect the others.
+///  - Latest version of all aur and devel packages - for offline use. The
+///    cache for a source that failed will be `None`.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (updates, cache) = check_all_updates_online().await;
+/// match updates.aur {
+///     Ok(aur) => println!("aur: {:#?}", aur),
+///     Err(e) => eprintln!("Failed to check aur updates: {e}"),
+/// }
+/// let offline = check_all_updates_offline(&cache).await;
+/// # };
+pub async fn check_all_updates_online() -> (AllUpdates, AllUpdatesCache) {
+    UpdateChecker::default().check_all_updates_online().await
+}
+
+/// Check if any pacman, AUR or devel packages have updates due, reporting
+/// progress as the check runs.
+///
+/// As per `check_all_updates_online()`, but a `Progress` event is sent to
+/// `progress` at each stage of the check. As the sources are checked
+/// concurrently, events from different sources may be interleaved.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # use futures::{channel::mpsc, StreamExt};
+/// # async {
+/// let (tx, rx) = mpsc::channel(16);
+/// let print_progress = rx.for_each(|progress| async move { println!("{progress}") });
+/// let ((updates, cache), _) =
+///     futures::join!(check_all_updates_online_with_progress(Some(tx)), print_progress);
+/// # };
+pub async fn check_all_updates_online_with_progress(
+    progress: Option<mpsc::Sender<Progress>>,
+) -> (AllUpdates, AllUpdatesCache) {
+    UpdateChecker::default()
+        .check_all_updates_online_with_progress(progress)
+        .await
+}
+
+/// Check if any pacman, AUR or devel packages have updates due, also
+/// returning how long each source took to check. This can help to diagnose
+/// which source is slow, e.g if checks are timing out.
+///
+/// As per `check_all_updates_online()`.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (updates, cache, timings) = check_all_updates_online_with_timings().await;
+/// println!("devel check took {:?}", timings.devel);
+/// # };
+pub async fn check_all_updates_online_with_timings() -> (AllUpdates, AllUpdatesCache, CheckTimings)
+{
+    UpdateChecker::default()
+        .check_all_updates_online_with_timings()
+        .await
+}
+
+pub(crate) async fn check_all_updates_online_impl(
+    backend: &impl AurBackend,
+    progress: &ProgressSender,
+) -> (AllUpdates, AllUpdatesCache, CheckTimings) {
+    let ((pacman, pacman_time), ((aur, aur_time), (devel, devel_time))) = futures::join!(
+        timed(async {
+            progress.report(Progress::QueryingPacman);
+            check_pacman_updates_online().await
+        }),
+        async {
+            // The installed foreign packages are shared by the AUR and devel
+            // checks, so only run `pacman -Qm` once.
+            let start = Instant::now();
+            progress.report(Progress::QueryingAur);
+            let aur_packages = backend.aur_packages().await;
+            let devel_packages = match &aur_packages {
+                Ok(aur_packages) => Ok(filter_devel_packages(aur_packages.clone())),
+                // Error isn't Clone, so let the devel check get its own.
+                Err(_) => backend.aur_packages().await.map(filter_devel_packages),
+            };
+            futures::join!(
+                async {
+                    let aur = match aur_packages {
+                        Ok(aur_packages) => get_aur_updates(backend, aur_packages).await,
+                        Err(e) => Err(e),
+                    };
+                    (aur, start.elapsed())
+                },
+                async {
+                    let devel = match devel_packages {
+                        Ok(devel_packages) => {
+                            check_devel_packages(backend, devel_packages, false, progress).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (devel, start.elapsed())
+                },
+            )
+        },
+    );
+    let (aur, aur_cache) =
+        split_result_and_cache(aur.map(|(updates, cache, orphaned)| (updates, (cache, orphaned))));
+    let (aur_cache, orphaned) = aur_cache.unzip();
+    let (devel, devel_cache) = split_result_and_cache(
+        devel.map(|(updates, cache, warnings)| (updates, (cache, warnings))),
+    );
+    let (devel_cache, warnings) = devel_cache.unzip();
+    (
+        AllUpdates {
+            pacman,
+            aur,
+            devel,
+            warnings: warnings.unwrap_or_default(),
+        },
+        AllUpdatesCache {
+            aur: aur_cache,
+            devel: devel_cache,
+            orphaned,
+        },
+        CheckTimings {
+            pacman: pacman_time,
+            aur: aur_time,
+            devel: devel_time,
+        },
+    )
+}
+
+/// Check only the packages in `pkgnames` for updates, e.g to check if a few
+/// critical packages are up to date without checking the whole system.
+///
+/// Online version - this function checks the network.
+/// Each package is checked against the source it was installed from - pacman
+/// packages with `checkupdates`, foreign packages against the AUR, and devel
+/// packages against their source repositories. Packages that aren't installed,
+/// or have no update due, aren't returned.
+///
+/// # Notes
+///  - `checkupdates` always syncs the whole database, so the pacman check isn't
+///    any faster than `check_pacman_updates_online()`. The AUR and devel checks
+///    only query the specified packages.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let updates = check_specified_packages(&["linux", "paru"]).await;
+/// if !updates.pacman.unwrap().is_empty() {
+///     println!("Kernel update due");
+/// }
+/// # };
+pub async fn check_specified_packages(pkgnames: &[&str]) -> AllUpdates {
+    UpdateChecker::default()
+        .check_specified_packages(pkgnames)
+        .await
+}
+
+pub(crate) async fn check_specified_packages_impl(
+    backend: &impl AurBackend,
+    pkgnames: &[&str],
+) -> AllUpdates {
+    let is_specified = |pkgname: &str| pkgnames.contains(&pkgname);
+    let (pacman, (aur, devel)) = futures::join!(
+        async {
+            let updates = check_pacman_updates_online().await?;
+            Ok(updates
+                .into_iter()
+                .filter(|update| is_specified(&update.pkgname))
+                .collect())
+        },
+        async {
+            let aur_packages = backend.aur_packages().await.map(|aur_packages| {
+                aur_packages
+                    .into_iter()
+                    .filter(|package| is_specified(&package.pkgname))
+                    .collect::<Vec<_>>()
+            });
+            let devel_packages = match &aur_packages {
+                Ok(aur_packages) => Ok(filter_devel_packages(aur_packages.clone())),
+                // Error isn't Clone, so let the devel check get its own.
+                Err(_) => backend.aur_packages().await.map(|aur_packages| {
+                    filter_devel_packages(aur_packages)
+                        .into_iter()
+                        .filter(|package| is_specified(&package.pkgname))
+                        .collect()
+                }),
+            };
+            futures::join!(
+                async { get_aur_updates(backend, aur_packages?).await },
+                async {
+                    check_devel_packages(
+                        backend,
+                        devel_packages?,
+                        false,
+                        &ProgressSender::default(),
+                    )
+                    .await
+                },
+            )
+        },
+    );
+    let (devel, warnings) = match devel {
+        Ok((updates, _, warnings)) => (Ok(updates), warnings),
+        Err(e) => (Err(e), Vec::new()),
+    };
+    AllUpdates {
+        pacman,
+        aur: aur.map(|(updates, _, _)| updates),
+        devel,
+        warnings,
+    }
+}
+
+/// Update status of a single package, from `check_package_update()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PackageUpdateStatus {
+    /// The package is installed, and has no update due.
+    UpToDate,
+    /// A pacman update is due.
+    Pacman(Update),
+    /// An AUR update is due.
+    Aur(AurUpdate),
+    /// A devel update is due.
+    Devel(DevelUpdate),
+    /// The package isn't installed.
+    NotInstalled,
+    /// The package is from the AUR, and wasn't checked as
+    /// `UpdateCheckerOptions::offline_only` is set.
+    NotChecked,
+}
+
+/// Check a single installed package for updates, e.g for a widget showing one
+/// watched package.
+///
+/// Online version - this function checks the network.
+/// Whether the package is a pacman, AUR or devel package is determined
+/// automatically, and only that source is checked.
+///
+/// # Notes
+///  - `checkupdates` always syncs the whole database, so checking a pacman
+///    package isn't any faster than `check_pacman_updates_online()`.
+///  - For a devel package with multiple source repositories, only the first
+///    update due is returned.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// match check_package_update("paru").await.unwrap() {
+///     PackageUpdateStatus::Aur(update) => println!("paru {} available", update.pkgver_new),
+///     PackageUpdateStatus::NotInstalled => println!("paru isn't installed"),
+///     _ => (),
+/// }
+/// # };
+pub async fn check_package_update(pkgname: &str) -> Result<PackageUpdateStatus> {
+    UpdateChecker::default().check_package_update(pkgname).await
+}
+
+pub(crate) async fn check_package_update_impl(
+    backend: &impl AurBackend,
+    pkgname: &str,
+    offline_only: bool,
+) -> Result<PackageUpdateStatus> {
+    let aur_package = backend
+        .aur_packages()
+        .await?
+        .into_iter()
+        .find(|package| package.pkgname == pkgname);
+    let status = match aur_package {
+        // Checking AUR and devel packages requires the network.
+        Some(_) if offline_only => return Ok(PackageUpdateStatus::NotChecked),
+        Some(package) if is_devel_package(&package.pkgname) => {
+            let (updates, _, _) =
+                check_devel_packages(backend, vec![package], false, &ProgressSender::default())
+                    .await?;
+            updates.into_iter().next().map(PackageUpdateStatus::Devel)
+        }
+        Some(package) => {
+            let (updates, _, _) = get_aur_updates(backend, vec![package]).await?;
+            updates.into_iter().next().map(PackageUpdateStatus::Aur)
+        }
+        None => {
+            if !is_installed(pkgname).await? {
+                return Ok(PackageUpdateStatus::NotInstalled);
+            }
+            let updates = if offline_only {
+                check_pacman_updates_offline().await?
+            } else {
+                check_pacman_updates_online().await?
+            };
+            updates
+                .into_iter()
+                .find(|update| update.pkgname == pkgname)
+                .map(PackageUpdateStatus::Pacman)
+        }
+    };
+    Ok(status.unwrap_or(PackageUpdateStatus::UpToDate))
+}
+
+/// Helper function - true if `pkgname` is installed, as per `pacman -Q`.
+async fn is_installed(pkgname: &str) -> Result<bool> {
+    let output = Command::new("pacman")
+        .arg("-Q")
+        .arg(pkgname)
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    Ok(output.status.success())
+}
+
+/// Helper function - also returns how long a future took to complete.
+async fn timed<T>(f: impl Future<Output = T>) -> (T, Duration) {
+    let start = Instant::now();
+    let output = f.await;
+    (output, start.elapsed())
+}
+
+/// Check if any pacman, AUR or devel packages have updates due.
+///
+/// Offline version - this function doesn't use the network, but needs a
+/// reference to the cache returned from `check_all_updates_online()`. If the
+/// cache for a source is `None`, that source will return `Error::NoCache`.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (_, cache) = check_all_updates_online().await;
+/// // Run `paru -Syu` in the terminal
+/// let offline = check_all_updates_offline(&cache).await;
+/// assert!(offline.pacman.unwrap().is_empty());
+/// # };
+pub async fn check_all_updates_offline(cache: &AllUpdatesCache) -> AllUpdates {
+    let (pacman, aur, devel) = futures::join!(
+        check_pacman_updates_offline(),
+        async {
+            match &cache.aur {
+                Some(aur_cache) => check_aur_updates_offline(aur_cache).await,
+                None => Err(Error::NoCache),
+            }
+        },
+        async {
+            match &cache.devel {
+                Some(devel_cache) => check_devel_updates_offline(devel_cache).await,
+                None => Err(Error::NoCache),
+            }
+        },
+    );
+    AllUpdates {
+        pacman,
+        aur,
+        devel,
+        warnings: Vec::new(),
+    }
+}
+
+fn split_result_and_cache<T, C>(result: Result<(T, C)>) -> (Result<T>, Option<C>) {
+    match result {
+        Ok((t, cache)) => (Ok(t), Some(cache)),
+        Err(e) => (Err(e), None),
+    }
+}
+
+/// Returns true if a DevelUpdate is due.
+/// VCS pkgvers embed the commit hash in different ways (e.g `r47.g72b934e1`,
+/// or a full length hash), so each component of the pkgver that looks like a
+/// hash is compared against the remote ref over their common length.
+fn devel_update_due(update: &DevelUpdate) -> bool {
+    let ref_id_new = update.ref_id_new.to_lowercase();
+    let pkgver_cur = update.pkgver_cur.to_lowercase();
+    if pkgver_cur.contains(&ref_id_new) {
+        return false;
+    }
+    !pkgver_cur
+        .split(['.', '_', '+', '-'])
+        .map(|component| {
+            // `git describe` style versions prefix the hash with a g.
+            component
+                .strip_prefix('g')
+                .filter(|hash| is_hash(hash))
+                .unwrap_or(component)
+        })
+        .filter(|component| is_hash(component))
+        .any(|hash| {
+            let len = hash.len().min(ref_id_new.len());
+            len >= MIN_HASH_LEN && hash[..len] == ref_id_new[..len]
+        })
+}
+
+/// Minimum number of hex digits required to consider part of a pkgver to be a
+/// commit hash.
+const MIN_HASH_LEN: usize = 7;
+
+fn is_hash(s: &str) -> bool {
+    s.len() >= MIN_HASH_LEN && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Return true if an aur package is due for an update.
+/// Devel packages are never due, they are only handled by the devel check.
+fn aur_update_due(package: &AurUpdate) -> bool {
+    if is_devel_package(&package.pkgname) {
+        return false;
+    }
+    // If it's not possible to determine ordering for a package, it will be filtered
+    // out.
+    let Some(pkgver_new) = Version::from(&package.pkgver_new) else {
+        return false;
+    };
+    let Some(pkgver_old) = Version::from(&package.pkgver_cur) else {
+        return false;
+    };
+    pkgver_new > pkgver_old || (pkgver_new == pkgver_old && package.pkgrel_new > package.pkgrel_cur)
+}
+
+/// The commands and AUR requests the online AUR and devel checks rely on.
+/// `reqwest::Client` is the real implementation - this exists so that the
+/// checks can be tested against captured output in `test_data`.
+pub(crate) trait AurBackend {
+    /// As per `get_aur_packages()`.
+    async fn aur_packages(&self) -> Result<Vec<Package>>;
+    /// As per `get_aur_info()`.
+    async fn aur_info(&self, pkgnames: &[&str]) -> Result<Vec<raur::Package>>;
+    /// As per `get_aur_srcinfo()`.
+    async fn srcinfo(&self, pkgbase: &str) -> Result<Srcinfo>;
+    /// As per `get_head_identifier()`.
+    async fn head_identifier(&self, url: String, branch: Option<&str>) -> Result<(String, String)>;
+    /// As per `get_head_commit_date()`.
+    async fn head_commit_date(
+        &self,
+        url: &str,
+        branch: Option<&str>,
+    ) -> Result<DateTime<FixedOffset>>;
+}
+
+impl AurBackend for reqwest::Client {
+    async fn aur_packages(&self) -> Result<Vec<Package>> {
+        get_aur_packages().await
+    }
+    async fn aur_info(&self, pkgnames: &[&str]) -> Result<Vec<raur::Package>> {
+        get_aur_info(self, pkgnames).await
+    }
+    async fn srcinfo(&self, pkgbase: &str) -> Result<Srcinfo> {
+        get_aur_srcinfo(self, pkgbase).await
+    }
+    async fn head_identifier(&self, url: String, branch: Option<&str>) -> Result<(String, String)> {
+        get_head_identifier(url, branch).await
+    }
+    async fn head_commit_date(
+        &self,
+        url: &str,
+        branch: Option<&str>,
+    ) -> Result<DateTime<FixedOffset>> {
+        get_head_commit_date(url, branch).await
+    }
+}
+
+/// pacman conf has a list of packages that should be ignored by pacman. This
+/// command fetches their pkgnames.
+async fn get_ignored_packages() -> Result<Vec<String>> {
+    // I considered pacmanconf crate here, but it's sync, and does the same thing
+    // under the hood (runs pacman-conf) as a Command.
+    let output = Command::new("pacman-conf")
+        .arg("IgnorePkg")
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Get a list of all aur packages on the system.
+/// An AUR package is a package returned by `pacman -Qm` excluding ignored
+/// packages.
+///
+/// This doesn't use the network.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// for package in get_aur_packages().await.unwrap() {
+///     println!("{} {}-{}", package.pkgname, package.pkgver, package.pkgrel);
+/// }
+/// # };
+pub async fn get_aur_packages() -> Result<Vec<Package>> {
+    let (ignored_packages, output) = futures::join!(
+        get_ignored_packages(),
+        Command::new("pacman")
+            .arg("-Qm")
+            .kill_on_drop(true)
+            .output()
+    );
+    parse_pacman_qm_output(&output?.stdout, &ignored_packages?)
+}
+
+/// Helper function - parse the output of `pacman -Qm`, excluding ignored
+/// packages.
+/// Output is decoded lossily, as the fields we parse are ASCII and a single
+/// invalid byte shouldn't fail the whole check.
+fn parse_pacman_qm_output(output: &[u8], ignored_packages: &[String]) -> Result<Vec<Package>> {
+    String::from_utf8_lossy(output)
+        .lines()
+        // Filter out any ignored packages
+        .filter(|line| {
+            !ignored_packages
+                .iter()
+                .any(|ignored_package| line.contains(ignored_package))
+        })
+        .map(parse_pacman_qm)
+        .collect()
+}
+
+/// Get a list of all devel packages on the system.
+/// A devel package is an AUR package ending with one of the `DEVEL_SUFFIXES`.
+///
+/// This doesn't use the network.
+pub async fn get_devel_packages() -> Result<Vec<Package>> {
+    let aur_packages = get_aur_packages().await?;
+    Ok(filter_devel_packages(aur_packages))
+}
+
+/// Helper function - keep only the packages ending with one of the
+/// `DEVEL_SUFFIXES`.
+fn filter_devel_packages(aur_packages: Vec<Package>) -> Vec<Package> {
+    aur_packages
+        .into_iter()
+        .filter(|package| is_devel_package(&package.pkgname))
+        .collect()
+}
+
+/// Helper function - true if `pkgname` ends with one of the `DEVEL_SUFFIXES`.
+fn is_devel_package(pkgname: &str) -> bool {
+    DEVEL_SUFFIXES
+        .iter()
+        .any(|suffix| pkgname.to_lowercase().contains(suffix))
+}
+
+/// Parse output of pacman -Qm into a package.
+/// Example input: "watchman-bin 2024.04.15.00-1"
+///
+/// Some locally built packages have no pkgrel. Rather than failing the whole
+/// package list, pkgrel defaults to "1" for these.
+///
+/// Surrounding whitespace (including the `\r` from CRLF line endings) and
+/// repeated spaces between the fields are ignored.
+fn parse_pacman_qm(line: &str) -> Result<Package> {
+    let (pkgname, rest) = line
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| Error::ParseErrorPacman(line.to_string()))?;
+    let (pkgver, pkgrel) = parse_ver_and_rel_or_default(rest.trim_start());
+    Ok(Package {
+        pkgname: pkgname.to_owned(),
+        pkgver,
+        pkgrel,
+    })
+}
+
+/// As per `parse_ver_and_rel()`, but a version with no pkgrel (e.g from a
+/// locally built package) is given pkgrel "1" instead of failing.
+fn parse_ver_and_rel_or_default(version: &str) -> (String, String) {
+    parse_ver_and_rel(version).unwrap_or_else(|_| (version.to_string(), "1".to_string()))
+}
+
+/// Get and parse the .SRCINFO for an aur pkgbase.
+async fn get_aur_srcinfo(client: &reqwest::Client, pkgbase: &str) -> Result<Srcinfo> {
+    let url = format!("https://aur.archlinux.org/cgit/aur.git/plain/.SRCINFO?h={pkgbase}");
+    let raw = client.get(url).send().await?.text().await?;
+    Ok(Srcinfo::from_str(&raw)?)
+}
+
+/// Maximum number of packages to request in a single AUR RPC info request.
+/// The AUR rejects requests that are too large, so larger lists are split.
+const AUR_INFO_BATCH_SIZE: usize = 150;
+
+/// Maximum number of AUR RPC info requests to have in flight at once.
+const AUR_INFO_MAX_CONCURRENT: usize = 4;
+
+/// Response to an AUR RPC request.
+#[derive(serde::Deserialize)]
+struct AurRpcResponse {
+    #[serde(rename = "type")]
+    response_type: String,
+    results: Vec<raur::Package>,
+}
+
+/// Get AUR info for a list of pkgnames, split into batches of at most
+/// AUR_INFO_BATCH_SIZE packages.
+/// The request is made here rather than by `raur::Handle`, as raur uses an
+/// older version of reqwest, so can't share our client.
+async fn get_aur_info(client: &reqwest::Client, pkgnames: &[&str]) -> Result<Vec<raur::Package>> {
+    in_batches(pkgnames, |batch| async move {
+        let mut params = batch
+            .iter()
+            .map(|name| ("arg[]", *name))
+            .collect::<Vec<_>>();
+        params.extend([("v", "5"), ("type", "info")]);
+        let response = async {
+            client
+                .post(raur::AUR_RPC_URL)
+                .form(&params)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<AurRpcResponse>()
+                .await
+        };
+        match response.await {
+            Ok(response) if response.response_type != "error" => Ok(response.results),
+            _ => Err(Error::GetAurPackageFailed(None)),
+        }
+    })
+    .await
+}
+
+/// Run `f` on each batch of at most AUR_INFO_BATCH_SIZE items, with at most
+/// AUR_INFO_MAX_CONCURRENT running at once, and merge the results in order.
+async fn in_batches<'a, T, F, Fut>(items: &'a [&'a str], f: F) -> Result<Vec<T>>
+where
+    F: Fn(&'a [&'a str]) -> Fut,
+    Fut: Future<Output = Result<Vec<T>>>,
+{
+    Ok(futures::stream::iter(items.chunks(AUR_INFO_BATCH_SIZE))
+        .map(f)
+        .buffered(AUR_INFO_MAX_CONCURRENT)
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Get the AUR pkgbase for each package, grouping packages built from the same
+/// pkgbase (split packages) together, so that each pkgbase only needs to be
+/// checked once.
+/// Packages that aren't on the AUR are returned separately.
+async fn get_pkgbases(
+    backend: &impl AurBackend,
+    packages: Vec<Package>,
+) -> Result<(Vec<(String, Vec<Package>)>, Vec<Package>)> {
+    if packages.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let info = backend
+        .aur_info(
+            &packages
+                .iter()
+                .map(|pkg| pkg.pkgname.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .await?;
+    Ok(group_by_pkgbase(
+        packages,
+        &info
+            .iter()
+            .map(|info| (info.name.as_str(), info.package_base.as_str()))
+            .collect(),
+    ))
+}
+
+/// Group packages by pkgbase, preserving the order packages were provided in.
+/// `pkgbases` is a map of pkgname to pkgbase.
+/// Returns a tuple of:
+///  - Packages grouped by pkgbase.
+///  - Packages not in `pkgbases` (i.e no longer on the AUR).
+fn group_by_pkgbase(
+    packages: Vec<Package>,
+    pkgbases: &HashMap<&str, &str>,
+) -> (Vec<(String, Vec<Package>)>, Vec<Package>) {
+    let mut groups: Vec<(String, Vec<Package>)> = Vec::new();
+    let mut orphaned = Vec::new();
+    for package in packages {
+        let Some(pkgbase) = pkgbases.get(package.pkgname.as_str()) else {
+            orphaned.push(package);
+            continue;
+        };
+        match groups.iter_mut().find(|(group, _)| group == pkgbase) {
+            Some((_, group_packages)) => group_packages.push(package),
+            None => groups.push((pkgbase.to_string(), vec![package])),
+        }
+    }
+    (groups, orphaned)
+}
+
+/// Get the git remotes (and branch, if specified) from the sources in a
+/// .SRCINFO. Each remote is only returned once, even if it's listed more than
+/// once (e.g for different architectures).
+fn source_remotes(srcinfo: &Srcinfo) -> Vec<(String, Option<String>)> {
+    let mut remotes = Vec::new();
+    for url in srcinfo.base.source.iter().flat_map(|arch| &arch.vec) {
+        let Some(PackageUrl { remote, branch, .. }) = parse_url(url) else {
+            continue;
+        };
+        let remote = (remote, branch.map(ToString::to_string));
+        if !remotes.contains(&remote) {
+            remotes.push(remote);
+        }
+    }
+    remotes
+}
+
+/// Remove duplicate devel updates for the same package and ref, keeping the
+/// first.
+fn dedup_devel_updates(updates: Vec<DevelUpdate>) -> Vec<DevelUpdate> {
+    let mut seen = BTreeSet::new();
+    updates
+        .into_iter()
+        .filter(|update| seen.insert((update.pkgname.clone(), update.ref_id_new.clone())))
+        .collect()
+}
+
+/// Get head identifier for a git repo - first 7 digits from commit hash, and
+/// the full commit hash.
+/// If a branch is not provided, HEAD will be selected.
+async fn get_head_identifier(url: String, branch: Option<&str>) -> Result<(String, String)> {
+    let output = Command::new("git")
+        .args(["ls-remote", &url, branch.unwrap_or("HEAD")])
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    parse_ls_remote(str::from_utf8(output.stdout.as_ref())?, branch)
+}
+
+/// Get the commit date of the head of a git repo.
+/// If a branch is not provided, HEAD will be selected.
+/// git ls-remote doesn't provide dates, so this does a shallow fetch (without
+/// trees or blobs, if the remote supports it) into a temporary repository.
+async fn get_head_commit_date(url: &str, branch: Option<&str>) -> Result<DateTime<FixedOffset>> {
+    let dir = tempfile::tempdir()?;
+    Command::new("git")
+        .args(["init", "--bare", "--quiet"])
+        .arg(dir.path())
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    Command::new("git")
+        .arg("-C")
+        .arg(dir.path())
+        .args(["fetch", "--quiet", "--depth=1", "--filter=tree:0", url])
+        .arg(branch.unwrap_or("HEAD"))
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir.path())
+        .args(["log", "-1", "--format=%cI", "FETCH_HEAD"])
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    let date = str::from_utf8(output.stdout.as_ref())?.trim();
+    DateTime::parse_from_rfc3339(date).map_err(|_| Error::ParseErrorCommitDate(date.to_string()))
+}
+
+/// Parse output of git ls-remote, returning the first 7 digits of the commit
+/// hash, and the full commit hash, for the requested branch (or HEAD if no
+/// branch is provided).
+/// Since ls-remote pattern matches refs, the output can contain multiple lines
+/// (e.g a tag with the same name as the branch), so the line for the exact ref
+/// is used.
+/// Example input: "1a2b3c4d5e6f...\trefs/heads/main"
+fn parse_ls_remote(output: &str, branch: Option<&str>) -> Result<(String, String)> {
+    let expected_ref = match branch {
+        Some(branch) => format!("refs/heads/{branch}"),
+        None => "HEAD".to_string(),
+    };
+    let hash = output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .find(|(_, ref_name)| *ref_name == expected_ref)
+        .map(|(hash, _)| hash)
+        .ok_or_else(|| Error::RefNotFound(expected_ref))?;
+    let short = hash
+        .get(0..7)
+        .ok_or_else(|| Error::HeadIdentifierTooShort)?;
+    Ok((short.to_string(), hash.to_string()))
+}
+
+/// Parse source field from .SRCINFO
+// NOTE: This is from paru (GPL3)
+fn parse_url(source: &str) -> Option<PackageUrl> {
+    let url = source.splitn(2, "::").last().unwrap();
+
+    if !url.starts_with("git") || !url.contains("://") {
+        return None;
+    }
+
+    let mut split = url.splitn(2, "://");
+    let protocol = split.next().unwrap();
+    let protocol = protocol.rsplit('+').next().unwrap();
+    let rest = split.next().unwrap();
+
+    let mut split = rest.splitn(2, '#');
+    let remote = split.next().unwrap();
+    let remote = remote.split_once('?').map_or(remote, |x| x.0);
+    let remote = format!("{}://{}", protocol, remote);
+
+    let branch = if let Some(fragment) = split.next() {
+        let fragment = fragment.split_once('?').map_or(fragment, |x| x.0);
+        let mut split = fragment.splitn(2, '=');
+        let frag_type = split.next().unwrap();
+
+        match frag_type {
+            "commit" | "tag" => return None,
+            "branch" => split.next(),
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    Some(PackageUrl {
+        remote,
+        protocol,
+        branch,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        aur_update_due, check_all_updates_offline, check_all_updates_online,
+        check_aur_updates_offline, check_aur_updates_online, check_devel_updates_offline,
+        check_devel_updates_online, check_specified_packages, dedup_devel_updates,
+        devel_update_due, filter_devel_packages, get_aur_srcinfo, get_aur_updates,
+        get_head_identifier, get_pkgbases, group_by_pkgbase, in_batches, orphaned_packages,
+        parse_ls_remote, parse_url, source_remotes, timed, uncached_packages, AllUpdates,
+        AllUpdatesCache, AurRpcResponse, AurUpdate, DevelUpdate, PackageUrl, UpdateDiff,
+        AUR_INFO_BATCH_SIZE,
+    };
+    use super::{
+        check_aur_updates_online_impl, check_devel_packages, check_devel_updates_online_impl,
+        check_orphaned_aur_packages_impl, check_package_update, check_package_update_impl,
+        parse_pacman_qm, parse_pacman_qm_output, parse_ver_and_rel_or_default, AurBackend, Package,
+        PackageUpdateStatus, ProgressSender, Warning,
+    };
+    use crate::Error;
+    use chrono::{DateTime, FixedOffset};
+    use srcinfo::Srcinfo;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_check_aur_updates() {
+        let (online, cache) = check_aur_updates_online().await.unwrap();
+        let offline = check_aur_updates_offline(&cache).await.unwrap();
+        assert_eq!(online, offline);
+        eprintln!("aur {:#?}", online);
+    }
+    #[tokio::test]
+    async fn test_check_devel_updates() {
+        let (online, cache) = check_devel_updates_online().await.unwrap();
+        let offline = check_devel_updates_offline(&cache).await.unwrap();
+        assert_eq!(online, offline);
+        eprintln!("devel {:#?}", online);
+    }
+    #[tokio::test]
+    async fn test_check_all_updates() {
+        let (online, cache) = check_all_updates_online().await;
+        let offline = check_all_updates_offline(&cache).await;
+        assert_eq!(online.pacman.unwrap(), offline.pacman.unwrap());
+        assert_eq!(online.aur.unwrap(), offline.aur.unwrap());
+        assert_eq!(online.devel.unwrap(), offline.devel.unwrap());
+    }
+    #[tokio::test]
+    async fn test_check_package_update_not_installed() {
+        let status = check_package_update("not-a-real-package").await.unwrap();
+        assert_eq!(status, PackageUpdateStatus::NotInstalled);
+    }
+    #[tokio::test]
+    async fn test_check_specified_packages() {
+        let updates = check_specified_packages(&["hyprlang-git"]).await;
+        assert!(updates.pacman.unwrap().is_empty());
+        assert!(updates.aur.unwrap().is_empty());
+        eprintln!("devel {:#?}", updates.devel.unwrap());
+    }
+    #[tokio::test]
+    async fn test_get_srcinfo() {
+        get_aur_srcinfo(&reqwest::Client::new(), "hyprlang-git")
+            .await
+            .unwrap();
+    }
+    #[tokio::test]
+    async fn test_get_url() {
+        let srcinfo = get_aur_srcinfo(&reqwest::Client::new(), "hyprlang-git")
+            .await
+            .unwrap();
+        let url = srcinfo.base.source.first().unwrap().vec.first().unwrap();
+        parse_url(url).unwrap();
+    }
+    #[tokio::test]
+    async fn test_get_head() {
+        let srcinfo = get_aur_srcinfo(&reqwest::Client::new(), "hyprutils-git")
+            .await
+            .unwrap();
+        let url = srcinfo.base.source.first().unwrap().vec.first().unwrap();
+        let url_parsed = parse_url(url).unwrap();
+        get_head_identifier(url_parsed.remote, url_parsed.branch)
+            .await
+            .unwrap();
+    }
+
+    fn devel_update(pkgver_cur: &str, ref_id_new: &str) -> DevelUpdate {
+        DevelUpdate {
+            pkgname: "test-git".to_string(),
+            pkgver_cur: pkgver_cur.to_string(),
+            pkgrel_cur: "1".to_string(),
+            ref_id_new: ref_id_new.to_string(),
+            ref_id_full: ref_id_new.to_string(),
+            remote_commit_date: None,
+            checked_at: DateTime::UNIX_EPOCH,
+            pkgbase: None,
+        }
+    }
+    #[test]
+    fn test_parse_pacman_qm_fixture() {
+        let packages = include_str!("../test_data/pacman_qm.txt")
+            .lines()
+            .map(parse_pacman_qm)
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(packages.len(), 4);
+        let expected = Package {
+            pkgname: "hyprlang-git".to_string(),
+            pkgver: "0.6.0.r2.g1a2b3c4".to_string(),
+            pkgrel: "1".to_string(),
+        };
+        assert_eq!(packages[1], expected);
+    }
+    #[test]
+    fn test_filter_devel_packages_fixture() {
+        let packages = include_str!("../test_data/pacman_qm.txt")
+            .lines()
+            .map(parse_pacman_qm)
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        let devel_packages = filter_devel_packages(packages);
+        assert_eq!(devel_packages.len(), 1);
+        assert_eq!(devel_packages[0].pkgname, "hyprlang-git");
+    }
+    #[test]
+    fn test_aur_rpc_response_fixture() {
+        let response: AurRpcResponse =
+            serde_json::from_str(include_str!("../test_data/aur_rpc_info.json")).unwrap();
+        assert_eq!(response.response_type, "multiinfo");
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.results[0].name, "paru");
+        assert_eq!(response.results[0].package_base, "paru");
+        assert_eq!(response.results[0].version, "2.0.4-1");
+        assert_eq!(response.results[0].out_of_date, None);
+    }
+    #[test]
+    fn test_orphaned_packages() {
+        let response: AurRpcResponse =
+            serde_json::from_str(include_str!("../test_data/aur_rpc_info.json")).unwrap();
+        let packages = vec![package("paru"), package("deleted-package")];
+        assert_eq!(
+            orphaned_packages(&packages, &response.results),
+            vec![package("deleted-package")]
+        );
+    }
+    #[test]
+    fn test_source_remotes_fixture() {
+        let srcinfo: Srcinfo = include_str!("../test_data/hyprlang-git.SRCINFO")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            source_remotes(&srcinfo),
+            vec![("https://github.com/hyprwm/hyprlang.git".to_string(), None)]
+        );
+    }
+    #[test]
+    fn test_parse_ls_remote_fixture() {
+        let output = include_str!("../test_data/ls_remote.txt");
+        let (id, _) = parse_ls_remote(output, None).unwrap();
+        assert_eq!(id, "9f8e7d6");
+        let (id, _) = parse_ls_remote(output, Some("main-old")).unwrap();
+        assert_eq!(id, "1a2b3c4");
+    }
+    #[test]
+    fn test_source_remotes_duplicate() {
+        let srcinfo: Srcinfo = "\
+pkgbase = foo-git
+\tpkgver = 1.0.r1.gabcdef1
+\tpkgrel = 1
+\tarch = x86_64
+\tsource = foo::git+https://github.com/foo/foo.git
+\tsource_x86_64 = git+https://github.com/foo/foo.git
+
+pkgname = foo-git
+"
+        .parse()
+        .unwrap();
+        assert_eq!(
+            source_remotes(&srcinfo),
+            vec![("https://github.com/foo/foo.git".to_string(), None)]
+        );
+    }
+    #[test]
+    fn test_uncached_packages() {
+        let cache = vec![AurUpdate {
+            pkgname: "paru".to_string(),
+            pkgver_cur: "2.0.3".to_string(),
+            pkgrel_cur: "1".to_string(),
+            pkgver_new: "2.0.4".to_string(),
+            pkgrel_new: "1".to_string(),
+            flagged_out_of_date: None,
+            pkgbase: None,
+        }];
+        let uncached = uncached_packages(vec![package("paru"), package("yay")], &cache);
+        assert_eq!(uncached, vec![package("yay")]);
+    }
+    #[test]
+    fn test_dedup_devel_updates() {
+        let updates = vec![
+            devel_update("1.0.r1.gabcdef1", "1234567"),
+            devel_update("1.0.r1.gabcdef1", "7654321"),
+            devel_update("1.0.r1.gabcdef1", "1234567"),
+        ];
+        assert_eq!(
+            dedup_devel_updates(updates),
+            vec![
+                devel_update("1.0.r1.gabcdef1", "1234567"),
+                devel_update("1.0.r1.gabcdef1", "7654321"),
+            ]
+        );
+    }
+    #[test]
+    fn test_devel_update_not_due_longer_installed_hash() {
+        let update = devel_update("20240105.r47.g72b934e1", "72b934e");
+        assert!(!devel_update_due(&update));
+    }
+    #[test]
+    fn test_devel_update_not_due_git_describe() {
+        let update = devel_update("0.6.0.r2.g1a2b3c4", "1a2b3c4");
+        assert!(!devel_update_due(&update));
+    }
+    #[test]
+    fn test_devel_update_not_due_full_hash() {
+        let update = devel_update("r10.1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b", "1a2b3c4");
+        assert!(!devel_update_due(&update));
+    }
+    #[test]
+    fn test_devel_update_not_due_uppercase() {
+        let update = devel_update("1.2.r5.gABCDEF0", "abcdef0");
+        assert!(!devel_update_due(&update));
+    }
+    #[test]
+    fn test_devel_update_due() {
+        let update = devel_update("20240105.r47.g72b934e1", "1a2b3c4");
+        assert!(devel_update_due(&update));
+    }
+    #[test]
+    fn test_devel_update_due_no_hash() {
+        let update = devel_update("20240105", "1a2b3c4");
+        assert!(devel_update_due(&update));
+    }
+    fn aur_update(pkgname: &str, pkgver_new: &str) -> AurUpdate {
+        AurUpdate {
+            pkgname: pkgname.to_string(),
+            pkgver_cur: "1.0".to_string(),
+            pkgrel_cur: "1".to_string(),
+            pkgver_new: pkgver_new.to_string(),
+            pkgrel_new: "1".to_string(),
+            flagged_out_of_date: None,
+            pkgbase: None,
+        }
+    }
+    #[test]
+    fn test_devel_package_not_aur_update_due() {
+        let update = aur_update("hyprlang-git", "0.7.0");
+        assert!(!aur_update_due(&update));
+        let devel_packages = filter_devel_packages(vec![package("hyprlang-git"), package("paru")]);
+        assert_eq!(devel_packages, vec![package("hyprlang-git")]);
+    }
+    #[test]
+    fn test_aur_update_is_pkgrel_only() {
+        assert!(aur_update("foo", "1.0").is_pkgrel_only());
+        assert!(!aur_update("foo", "1.1").is_pkgrel_only());
+    }
+    #[test]
+    fn test_cache_diff() {
+        let old = AllUpdatesCache {
+            aur: Some(vec![aur_update("foo", "1.1"), aur_update("bar", "1.1")]),
+            devel: Some(vec![devel_update("20240105.r47.g72b934e1", "72b934e")]),
+            orphaned: Some(Vec::new()),
+        };
+        let new = AllUpdatesCache {
+            aur: Some(vec![aur_update("foo", "1.1"), aur_update("bar", "1.0")]),
+            devel: Some(vec![devel_update("20240105.r47.g72b934e1", "1a2b3c4")]),
+            orphaned: Some(Vec::new()),
+        };
+        let expected = UpdateDiff {
+            added: vec!["test-git".to_string()],
+            removed: vec!["bar".to_string()],
+        };
+        assert_eq!(old.diff(&new), expected);
+        assert_eq!(new.diff(&new), UpdateDiff::default());
+    }
+    #[test]
+    fn test_cache_diff_no_cache() {
+        let old = AllUpdatesCache::default();
+        let new = AllUpdatesCache {
+            aur: Some(vec![aur_update("foo", "1.1")]),
+            devel: None,
+            orphaned: Some(Vec::new()),
+        };
+        assert_eq!(old.diff(&new).added, vec!["foo".to_string()]);
+    }
+    fn package(pkgname: &str) -> Package {
+        Package {
+            pkgname: pkgname.to_string(),
+            pkgver: "1.0.r1.g1a2b3c4".to_string(),
+            pkgrel: "1".to_string(),
+        }
+    }
+    #[test]
+    fn test_group_by_pkgbase() {
+        let packages = vec![
+            package("foo-git"),
+            package("bar-git"),
+            package("foo-docs-git"),
+        ];
+        let pkgbases = HashMap::from([
+            ("foo-git", "foo-git"),
+            ("foo-docs-git", "foo-git"),
+            ("bar-git", "bar-git"),
+        ]);
+        let (groups, orphaned) = group_by_pkgbase(packages, &pkgbases);
+        let expected = vec![
+            (
+                "foo-git".to_string(),
+                vec![package("foo-git"), package("foo-docs-git")],
+            ),
+            ("bar-git".to_string(), vec![package("bar-git")]),
+        ];
+        assert_eq!(groups, expected);
+        assert!(orphaned.is_empty());
+    }
+    #[test]
+    fn test_group_by_pkgbase_not_in_aur() {
+        let pkgbases = HashMap::from([("foo-git", "foo-git")]);
+        let (groups, orphaned) =
+            group_by_pkgbase(vec![package("foo-git"), package("bar-git")], &pkgbases);
+        assert_eq!(
+            groups,
+            vec![("foo-git".to_string(), vec![package("foo-git")])]
+        );
+        assert_eq!(orphaned, vec![package("bar-git")]);
+    }
+    /// Backend that returns the captured output in `test_data`, so the online
+    /// checks can be run end to end without pacman or the network.
+    struct MockBackend {
+        pacman_qm: &'static str,
+        ls_remote: &'static str,
+    }
+    impl Default for MockBackend {
+        fn default() -> Self {
+            Self {
+                pacman_qm: include_str!("../test_data/pacman_qm.txt"),
+                ls_remote: include_str!("../test_data/ls_remote.txt"),
+            }
+        }
+    }
+    impl AurBackend for MockBackend {
+        async fn aur_packages(&self) -> crate::Result<Vec<Package>> {
+            parse_pacman_qm_output(self.pacman_qm.as_bytes(), &[])
+        }
+        async fn aur_info(&self, pkgnames: &[&str]) -> crate::Result<Vec<raur::Package>> {
+            Ok([
+                include_str!("../test_data/aur_rpc_info.json"),
+                include_str!("../test_data/aur_rpc_info_devel.json"),
+            ]
+            .into_iter()
+            .flat_map(|fixture| {
+                serde_json::from_str::<AurRpcResponse>(fixture)
+                    .unwrap()
+                    .results
+            })
+            .filter(|info| pkgnames.contains(&info.name.as_str()))
+            .collect())
+        }
+        async fn srcinfo(&self, pkgbase: &str) -> crate::Result<Srcinfo> {
+            assert_eq!(pkgbase, "hyprlang-git");
+            Ok(include_str!("../test_data/hyprlang-git.SRCINFO").parse()?)
+        }
+        async fn head_identifier(
+            &self,
+            url: String,
+            branch: Option<&str>,
+        ) -> crate::Result<(String, String)> {
+            assert_eq!(url, "https://github.com/hyprwm/hyprlang.git");
+            parse_ls_remote(self.ls_remote, branch)
+        }
+        async fn head_commit_date(
+            &self,
+            _: &str,
+            _: Option<&str>,
+        ) -> crate::Result<DateTime<FixedOffset>> {
+            Ok(DateTime::parse_from_rfc3339("2024-12-01T10:00:00+10:00").unwrap())
+        }
+    }
+    #[tokio::test]
+    async fn test_mock_aur_updates_up_to_date() {
+        let (updates, cache) = check_aur_updates_online_impl(&MockBackend::default())
+            .await
+            .unwrap();
+        assert!(updates.is_empty());
+        let cached = cache
+            .iter()
+            .map(|update| update.pkgname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(cached, vec!["paru", "hyprlang-git"]);
+    }
+    #[tokio::test]
+    async fn test_mock_aur_update_due() {
+        let backend = MockBackend {
+            pacman_qm: "paru 2.0.3-1\n",
+            ..Default::default()
+        };
+        let (updates, _) = check_aur_updates_online_impl(&backend).await.unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].pkgver_cur, "2.0.3");
+        assert_eq!(updates[0].pkgver_new, "2.0.4");
+        assert_eq!(updates[0].pkgbase.as_deref(), Some("paru"));
+        let status = check_package_update_impl(&backend, "paru", false)
+            .await
+            .unwrap();
+        assert_eq!(status, PackageUpdateStatus::Aur(updates[0].clone()));
+    }
+    #[tokio::test]
+    async fn test_mock_orphaned_aur_packages() {
+        let orphaned = check_orphaned_aur_packages_impl(&MockBackend::default())
+            .await
+            .unwrap();
+        let orphaned = orphaned
+            .iter()
+            .map(|package| package.pkgname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            orphaned,
+            vec!["cosmic-applet-arch", "visual-studio-code-bin"]
+        );
+    }
+    #[tokio::test]
+    async fn test_mock_devel_update_due() {
+        let (updates, cache) =
+            check_devel_updates_online_impl(&MockBackend::default(), true, &Default::default())
+                .await
+                .unwrap();
+        assert_eq!(updates, cache);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].pkgname, "hyprlang-git");
+        assert_eq!(updates[0].pkgver_cur, "0.6.0.r2.g1a2b3c4");
+        assert_eq!(updates[0].ref_id_new, "9f8e7d6");
+        assert_eq!(updates[0].pkgbase.as_deref(), Some("hyprlang-git"));
+        assert!(updates[0].remote_commit_date.is_some());
+    }
+    #[tokio::test]
+    async fn test_mock_devel_up_to_date() {
+        let backend = MockBackend {
+            ls_remote: "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b\tHEAD\n",
+            ..Default::default()
+        };
+        let (updates, cache) =
+            check_devel_updates_online_impl(&backend, false, &Default::default())
+                .await
+                .unwrap();
+        assert!(updates.is_empty());
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache[0].ref_id_new, "1a2b3c4");
+        assert_eq!(cache[0].remote_commit_date, None);
+    }
+    #[tokio::test]
+    async fn test_mock_devel_not_in_aur() {
+        let packages = vec![package("hyprlang-git"), package("deleted-git")];
+        let (_, cache, warnings) = check_devel_packages(
+            &MockBackend::default(),
+            packages,
+            false,
+            &ProgressSender::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(
+            warnings,
+            vec![Warning::DevelNotInAur("deleted-git".to_string())]
+        );
+    }
+    #[tokio::test]
+    async fn test_no_aur_packages() {
+        // Empty `pacman -Qm` output.
+        let packages = ""
+            .lines()
+            .map(parse_pacman_qm)
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        let client = reqwest::Client::new();
+        let (updates, cache, orphaned) = get_aur_updates(&client, packages.clone()).await.unwrap();
+        assert!(updates.is_empty());
+        assert!(cache.is_empty());
+        assert!(orphaned.is_empty());
+        let (groups, orphaned) = get_pkgbases(&client, packages).await.unwrap();
+        assert!(groups.is_empty());
+        assert!(orphaned.is_empty());
+    }
+    #[test]
+    fn test_all_updates_remove_pkgrel_only() {
+        let mut updates = AllUpdates {
+            pacman: Ok(vec![
+                crate::parse_update("libadwaita 1:1.6.1-1 -> 1:1.6.1-2").unwrap(),
+                crate::parse_update("linux 6.12.1-1 -> 6.12.2-1").unwrap(),
+            ]),
+            aur: Ok(vec![aur_update("foo", "1.0"), aur_update("bar", "1.1")]),
+            devel: Err(Error::NoCache),
+            warnings: Vec::new(),
+        };
+        updates.remove_pkgrel_only();
+        let pacman = updates.pacman.unwrap();
+        assert_eq!(pacman.len(), 1);
+        assert_eq!(pacman[0].pkgname, "linux");
+        assert_eq!(updates.aur.unwrap(), vec![aur_update("bar", "1.1")]);
+        assert!(updates.devel.is_err());
+    }
+    #[test]
+    fn test_all_updates_display() {
+        let mut updates = AllUpdates {
+            pacman: Ok(vec![]),
+            aur: Ok(vec![]),
+            devel: Ok(vec![]),
+            warnings: Vec::new(),
+        };
+        assert_eq!(updates.to_string(), "pacman: 0, aur: 0, devel: 0");
+        updates.aur = Err(Error::GetAurPackageFailed(None));
+        assert_eq!(updates.to_string(), "pacman: 0, aur: 0, devel: 0 (1 error)");
+        updates.devel = Err(Error::GetAurPackageFailed(None));
+        assert_eq!(updates.error_count(), 2);
+        assert_eq!(
+            updates.to_string(),
+            "pacman: 0, aur: 0, devel: 0 (2 errors)"
+        );
+    }
+    #[tokio::test]
+    async fn test_timed() {
+        let delay = std::time::Duration::from_millis(20);
+        let (output, elapsed) = timed(async {
+            tokio::time::sleep(delay).await;
+            1
+        })
+        .await;
+        assert_eq!(output, 1);
+        assert!(elapsed >= delay);
+    }
+    #[tokio::test]
+    async fn test_in_batches() {
+        let names = (0..1000).map(|i| format!("pkg{i}")).collect::<Vec<_>>();
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        let queried = in_batches(&names, |batch| async move {
+            assert!(batch.len() <= AUR_INFO_BATCH_SIZE);
+            Ok(batch.to_vec())
+        })
+        .await
+        .unwrap();
+        assert_eq!(queried, names);
+    }
+    #[test]
+    fn test_parse_url() {
+        let url = parse_url(
+            "paper-icon-theme::git+https://github.com/snwh/paper-icon-theme.git#branch=main",
+        )
+        .unwrap();
+        let expected = PackageUrl {
+            remote: "https://github.com/snwh/paper-icon-theme.git".to_string(),
+            protocol: "https",
+            branch: Some("main"),
+        };
+        assert_eq!(url, expected);
+    }
+    #[test]
+    fn test_parse_url_none() {
+        let url = parse_url(
+            "paper-icon-themegit:gopher://github.com/snwh/paper-icon-theme.git branch=main",
+        );
+        eprintln!("{:#?}", url);
+        assert!(url.is_none());
+    }
+    #[test]
+    fn test_parse_ls_remote_multiple_refs() {
+        let output = "\
+ad64a0e0d5a6e5f1f5c1c3a0c2cf8a6d1d2c9a1b\trefs/heads/main-old
+1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b\trefs/heads/main
+9f8e7d6c5b4a39281706f5e4d3c2b1a098765432\trefs/tags/main
+";
+        let (id, full) = parse_ls_remote(output, Some("main")).unwrap();
+        assert_eq!(id, "1a2b3c4");
+        assert_eq!(full, "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b");
+    }
+    #[test]
+    fn test_parse_ls_remote_head() {
+        let output = "\
+9f8e7d6c5b4a39281706f5e4d3c2b1a098765432\trefs/heads/HEAD-backup
+1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b\tHEAD
+";
+        let (id, full) = parse_ls_remote(output, None).unwrap();
+        assert_eq!(id, "1a2b3c4");
+        assert_eq!(full, "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b");
+    }
+    #[test]
+    fn test_parse_ls_remote_not_found() {
+        let output = "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b\trefs/tags/main\n";
+        let err = parse_ls_remote(output, Some("main")).unwrap_err();
+        match err {
+            Error::RefNotFound(s) => assert_eq!(s, "refs/heads/main"),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn test_parse_pacman_qm() {
+        let update = parse_pacman_qm("winetricks-git 20240105.r47.g72b934e1-2").unwrap();
+        let expected = Package {
+            pkgname: "winetricks-git".to_string(),
+            pkgver: "20240105.r47.g72b934e1".to_string(),
+            pkgrel: "2".to_string(),
+        };
+        assert_eq!(update, expected);
+    }
+    #[test]
+    fn test_parse_pacman_qm_no_pkgrel() {
+        let update = parse_pacman_qm("local-pkg 1.2.3").unwrap();
+        let expected = Package {
+            pkgname: "local-pkg".to_string(),
+            pkgver: "1.2.3".to_string(),
+            pkgrel: "1".to_string(),
+        };
+        assert_eq!(update, expected);
+    }
+    #[test]
+    fn test_parse_ver_and_rel_or_default() {
+        assert_eq!(
+            parse_ver_and_rel_or_default("2.0.4-1"),
+            ("2.0.4".to_string(), "1".to_string())
+        );
+        assert_eq!(
+            parse_ver_and_rel_or_default("2.0.4"),
+            ("2.0.4".to_string(), "1".to_string())
+        );
+    }
+    #[test]
+    fn test_parse_pacman_qm_whitespace() {
+        let expected = Package {
+            pkgname: "winetricks-git".to_string(),
+            pkgver: "20240105.r47.g72b934e1".to_string(),
+            pkgrel: "2".to_string(),
+        };
+        for line in [
+            "winetricks-git 20240105.r47.g72b934e1-2  ",
+            "winetricks-git 20240105.r47.g72b934e1-2\r",
+            "winetricks-git   20240105.r47.g72b934e1-2",
+        ] {
+            assert_eq!(parse_pacman_qm(line).unwrap(), expected);
+        }
+    }
+    #[test]
+    fn test_parse_pacman_qm_crlf() {
+        let packages = "paru 2.0.4-1\r\nwinetricks-git 20240105.r47.g72b934e1-2\r\n"
+            .lines()
+            .map(parse_pacman_qm)
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(packages[0].pkgrel, "1");
+        assert_eq!(packages[1].pkgrel, "2");
+    }
+    #[test]
+    fn test_parse_pacman_qm_invalid_utf8() {
+        let output = b"paru 2.0.4-1\nfoo-bin 1.0\xfe-1\nbar-bin 2.0-1\n";
+        let packages = parse_pacman_qm_output(output, &["bar-bin".to_string()]).unwrap();
+        let pkgnames = packages
+            .iter()
+            .map(|package| package.pkgname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(pkgnames, vec!["paru", "foo-bin"]);
+    }
+    #[test]
+    fn test_parse_pacman_qm_error() {
+        let str = "winetricks-git0240105.r47.g72b934e1-2";
+        let update = parse_pacman_qm(str).unwrap_err();
+        eprintln!("{:#?}", update);
+        match update {
+            Error::ParseErrorPacman(s) => assert_eq!(s, str),
+            _ => panic!(),
+        }
+    }
+}
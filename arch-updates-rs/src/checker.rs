@@ -0,0 +1,370 @@
+//! Configurable entry point for the online update checks.
+use crate::aur::{
+    check_all_updates_online_impl, check_aur_updates_online_impl, check_devel_updates_online_impl,
+    check_orphaned_aur_packages_impl, check_package_update_impl, check_specified_packages_impl,
+    ProgressSender,
+};
+use crate::{
+    check_pacman_updates_offline, AllUpdates, AllUpdatesCache, AurUpdate, CheckTimings,
+    DevelUpdate, Error, Package, PackageUpdateStatus, Progress, Result,
+};
+use futures::channel::mpsc;
+use std::time::Duration;
+
+/// User agent sent with HTTP requests by default.
+pub const DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Timeout for each HTTP request by default.
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Options used to build an `UpdateChecker`.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// let mut options = UpdateCheckerOptions::default();
+/// options.user_agent = "my-app/1.0".to_string();
+/// let checker = UpdateChecker::with_options(options).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct UpdateCheckerOptions {
+    /// User agent sent with all HTTP requests (AUR RPC and .SRCINFO fetches).
+    /// The AUR asks that clients set a descriptive user agent.
+    pub user_agent: String,
+    /// Proxy URL used for all HTTP requests (AUR RPC and .SRCINFO fetches),
+    /// e.g `http://proxy.example.com:8080`.
+    /// If `None`, or for requests that aren't matched by the proxy, the
+    /// `HTTP_PROXY`/`HTTPS_PROXY` environment variables are respected instead.
+    pub proxy: Option<String>,
+    /// Timeout for each individual HTTP request, so that one slow request
+    /// fails fast instead of holding up the whole check. `None` for no
+    /// timeout.
+    pub http_timeout: Option<Duration>,
+    /// Never access the network, e.g for use in CI or tests. If set:
+    ///  - the AUR and devel checks (and the orphaned packages check) return
+    ///    `Error::OfflineOnly` instead of results, including in the combined
+    ///    checks. No caches are returned, so a later offline check can't
+    ///    mistake a skipped check for every package being up to date.
+    ///  - the pacman part of the combined checks uses the local sync database,
+    ///    as per `check_pacman_updates_offline()`.
+    ///
+    /// `UpdateChecker::is_offline_only()` can be used to check for this mode up
+    /// front.
+    /// Local commands such as `pacman -Qm` are still run.
+    pub offline_only: bool,
+    /// Don't return pacman and AUR updates where only the pkgrel has changed,
+    /// i.e rebuilds without a new upstream version. Caches still contain every
+    /// package. See `AllUpdates::remove_pkgrel_only()` to do the same for the
+    /// offline checks.
+    pub ignore_pkgrel_only: bool,
+}
+
+impl Default for UpdateCheckerOptions {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+            http_timeout: Some(DEFAULT_HTTP_TIMEOUT),
+            offline_only: false,
+            ignore_pkgrel_only: false,
+        }
+    }
+}
+
+/// Runs the online AUR and devel checks, sharing state between them.
+///
+/// The free `check_*_online` functions each create a new `UpdateChecker`, so
+/// when checking repeatedly (e.g from an applet) it's more efficient to create
+/// one `UpdateChecker` and reuse it, so that HTTP connections can be reused.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let checker = UpdateChecker::new();
+/// let (updates, cache) = checker.check_all_updates_online().await;
+/// // Later, reusing the same connections.
+/// let (updates, cache) = checker.check_all_updates_online().await;
+/// # };
+/// ```
+#[derive(Clone, Debug)]
+pub struct UpdateChecker {
+    client: reqwest::Client,
+    offline_only: bool,
+    ignore_pkgrel_only: bool,
+}
+
+impl Default for UpdateChecker {
+    /// # Panics
+    /// As per `reqwest::Client::new()`, this panics if the HTTP client can't
+    /// be initialized.
+    fn default() -> Self {
+        Self::with_options(UpdateCheckerOptions::default())
+            .expect("HTTP client with default options should be valid")
+    }
+}
+
+impl UpdateChecker {
+    /// Create an `UpdateChecker` with its own HTTP client, using the default
+    /// options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Create an `UpdateChecker` with its own HTTP client, built using
+    /// `options`.
+    pub fn with_options(options: UpdateCheckerOptions) -> Result<Self> {
+        let UpdateCheckerOptions {
+            user_agent,
+            proxy,
+            http_timeout,
+            offline_only,
+            ignore_pkgrel_only,
+        } = options;
+        let mut builder = reqwest::Client::builder().user_agent(user_agent);
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(http_timeout) = http_timeout {
+            builder = builder.timeout(http_timeout);
+        }
+        Ok(Self {
+            client: builder.build()?,
+            offline_only,
+            ignore_pkgrel_only,
+        })
+    }
+    /// Create an `UpdateChecker` using a caller supplied HTTP client, e.g to
+    /// share a connection pool with the rest of an application.
+    ///
+    /// Note that the client is used as-is, so a descriptive user agent should
+    /// be set on it by the caller.
+    pub fn with_client(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            offline_only: false,
+            ignore_pkgrel_only: false,
+        }
+    }
+    /// As per `UpdateCheckerOptions::offline_only`, but for an existing
+    /// `UpdateChecker`.
+    pub fn set_offline_only(&mut self, offline_only: bool) {
+        self.offline_only = offline_only;
+    }
+    /// True if this `UpdateChecker` never accesses the network. See
+    /// `UpdateCheckerOptions::offline_only`.
+    pub fn is_offline_only(&self) -> bool {
+        self.offline_only
+    }
+    /// As per `UpdateCheckerOptions::ignore_pkgrel_only`, but for an existing
+    /// `UpdateChecker`.
+    pub fn set_ignore_pkgrel_only(&mut self, ignore_pkgrel_only: bool) {
+        self.ignore_pkgrel_only = ignore_pkgrel_only;
+    }
+    /// As per `check_aur_updates_online()`.
+    pub async fn check_aur_updates_online(&self) -> Result<(Vec<AurUpdate>, Vec<AurUpdate>)> {
+        if self.offline_only {
+            return Err(Error::OfflineOnly);
+        }
+        let (mut updates, cache) = check_aur_updates_online_impl(&self.client).await?;
+        if self.ignore_pkgrel_only {
+            updates.retain(|update| !update.is_pkgrel_only());
+        }
+        Ok((updates, cache))
+    }
+    /// As per `check_devel_updates_online()`.
+    pub async fn check_devel_updates_online(&self) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+        self.check_devel_updates(false, &ProgressSender::default())
+            .await
+    }
+    /// As per `check_devel_updates_online_with_dates()`.
+    pub async fn check_devel_updates_online_with_dates(
+        &self,
+    ) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+        self.check_devel_updates(true, &ProgressSender::default())
+            .await
+    }
+    /// As per `check_devel_updates_online_with_progress()`.
+    pub async fn check_devel_updates_online_with_progress(
+        &self,
+        progress: Option<mpsc::Sender<Progress>>,
+    ) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+        self.check_devel_updates(false, &ProgressSender::new(progress))
+            .await
+    }
+    /// As per `check_orphaned_aur_packages()`.
+    pub async fn check_orphaned_aur_packages(&self) -> Result<Vec<Package>> {
+        if self.offline_only {
+            return Err(Error::OfflineOnly);
+        }
+        check_orphaned_aur_packages_impl(&self.client).await
+    }
+    /// As per `check_all_updates_online()`.
+    pub async fn check_all_updates_online(&self) -> (AllUpdates, AllUpdatesCache) {
+        let (updates, cache, _) = self.check_all_updates(&ProgressSender::default()).await;
+        (updates, cache)
+    }
+    /// As per `check_all_updates_online_with_progress()`.
+    pub async fn check_all_updates_online_with_progress(
+        &self,
+        progress: Option<mpsc::Sender<Progress>>,
+    ) -> (AllUpdates, AllUpdatesCache) {
+        let (updates, cache, _) = self.check_all_updates(&ProgressSender::new(progress)).await;
+        (updates, cache)
+    }
+    /// As per `check_all_updates_online_with_timings()`.
+    pub async fn check_all_updates_online_with_timings(
+        &self,
+    ) -> (AllUpdates, AllUpdatesCache, CheckTimings) {
+        self.check_all_updates(&ProgressSender::default()).await
+    }
+    /// As per `check_specified_packages()`.
+    pub async fn check_specified_packages(&self, pkgnames: &[&str]) -> AllUpdates {
+        let mut updates = if self.offline_only {
+            let pacman = check_pacman_updates_offline().await.map(|updates| {
+                updates
+                    .into_iter()
+                    .filter(|update| pkgnames.contains(&update.pkgname.as_str()))
+                    .collect()
+            });
+            AllUpdates {
+                pacman,
+                aur: Err(Error::OfflineOnly),
+                devel: Err(Error::OfflineOnly),
+                warnings: Vec::new(),
+            }
+        } else {
+            check_specified_packages_impl(&self.client, pkgnames).await
+        };
+        if self.ignore_pkgrel_only {
+            updates.remove_pkgrel_only();
+        }
+        updates
+    }
+    /// As per `check_package_update()`.
+    ///
+    /// If `offline_only` is set, AUR and devel packages aren't checked, and are
+    /// reported as `PackageUpdateStatus::NotChecked`.
+    ///
+    /// If `ignore_pkgrel_only` is set, pacman and AUR packages where only the
+    /// pkgrel has changed are reported as `PackageUpdateStatus::UpToDate`.
+    pub async fn check_package_update(&self, pkgname: &str) -> Result<PackageUpdateStatus> {
+        let status = check_package_update_impl(&self.client, pkgname, self.offline_only).await?;
+        Ok(match status {
+            PackageUpdateStatus::Pacman(update)
+                if self.ignore_pkgrel_only && update.is_pkgrel_only() =>
+            {
+                PackageUpdateStatus::UpToDate
+            }
+            PackageUpdateStatus::Aur(update)
+                if self.ignore_pkgrel_only && update.is_pkgrel_only() =>
+            {
+                PackageUpdateStatus::UpToDate
+            }
+            status => status,
+        })
+    }
+    async fn check_devel_updates(
+        &self,
+        get_dates: bool,
+        progress: &ProgressSender,
+    ) -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
+        if self.offline_only {
+            return Err(Error::OfflineOnly);
+        }
+        check_devel_updates_online_impl(&self.client, get_dates, progress).await
+    }
+    async fn check_all_updates(
+        &self,
+        progress: &ProgressSender,
+    ) -> (AllUpdates, AllUpdatesCache, CheckTimings) {
+        let (mut updates, cache, timings) = if self.offline_only {
+            progress.report(Progress::QueryingPacman);
+            let updates = AllUpdates {
+                pacman: check_pacman_updates_offline().await,
+                aur: Err(Error::OfflineOnly),
+                devel: Err(Error::OfflineOnly),
+                warnings: Vec::new(),
+            };
+            // Nothing was checked, so there is nothing to cache. Empty caches would
+            // report every package as up to date in later offline checks.
+            (updates, AllUpdatesCache::default(), CheckTimings::default())
+        } else {
+            check_all_updates_online_impl(&self.client, progress).await
+        };
+        if self.ignore_pkgrel_only {
+            updates.remove_pkgrel_only();
+        }
+        (updates, cache, timings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UpdateChecker, UpdateCheckerOptions, DEFAULT_USER_AGENT};
+    use crate::{AllUpdates, Error};
+
+    #[test]
+    fn test_default_user_agent() {
+        assert!(DEFAULT_USER_AGENT.starts_with("arch-updates-rs/"));
+        UpdateChecker::new();
+    }
+    #[test]
+    fn test_invalid_user_agent() {
+        let options = UpdateCheckerOptions {
+            user_agent: "invalid\nuser agent".to_string(),
+            ..Default::default()
+        };
+        match UpdateChecker::with_options(options).unwrap_err() {
+            Error::Web(_) => (),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn test_proxy() {
+        let options = UpdateCheckerOptions {
+            proxy: Some("http://proxy.example.com:8080".to_string()),
+            ..Default::default()
+        };
+        UpdateChecker::with_options(options).unwrap();
+    }
+    #[test]
+    fn test_invalid_proxy() {
+        let options = UpdateCheckerOptions {
+            proxy: Some("not a proxy url".to_string()),
+            ..Default::default()
+        };
+        match UpdateChecker::with_options(options).unwrap_err() {
+            Error::Web(_) => (),
+            _ => panic!(),
+        }
+    }
+    #[tokio::test]
+    async fn test_offline_only() {
+        // Use an unroutable proxy, so that any attempted request would fail.
+        let options = UpdateCheckerOptions {
+            proxy: Some("http://127.0.0.1:9".to_string()),
+            offline_only: true,
+            ..Default::default()
+        };
+        let checker = UpdateChecker::with_options(options).unwrap();
+        assert!(checker.is_offline_only());
+        assert!(matches!(
+            checker.check_aur_updates_online().await,
+            Err(Error::OfflineOnly)
+        ));
+        assert!(matches!(
+            checker.check_devel_updates_online().await,
+            Err(Error::OfflineOnly)
+        ));
+        assert!(matches!(
+            checker.check_orphaned_aur_packages().await,
+            Err(Error::OfflineOnly)
+        ));
+        let (AllUpdates { aur, devel, .. }, cache) = checker.check_all_updates_online().await;
+        assert!(matches!(aur, Err(Error::OfflineOnly)));
+        assert!(matches!(devel, Err(Error::OfflineOnly)));
+        assert_eq!(cache.aur, None);
+        assert_eq!(cache.devel, None);
+        assert_eq!(cache.orphaned, None);
+    }
+}
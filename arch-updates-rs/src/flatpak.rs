@@ -0,0 +1,106 @@
+//! Checks for updates to installed flatpak applications and runtimes, for
+//! systems that use flatpak alongside pacman.
+use crate::{Error, Result};
+use tokio::process::Command;
+
+/// An installed flatpak application or runtime with an update available.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FlatpakUpdate {
+    /// Application or runtime ID, e.g `org.mozilla.firefox`.
+    pub app_id: String,
+    /// e.g `stable`.
+    pub branch: String,
+    /// Name of the remote the update is from, e.g `flathub`.
+    pub remote: String,
+}
+
+/// Use `flatpak remote-ls --updates` to check if any installed flatpak
+/// applications or runtimes have updates due, in both the user and system
+/// installations.
+///
+/// Online version - this function uses the network. There is no offline
+/// version, as flatpak doesn't record which updates were due.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let updates = check_flatpak_updates_online().await.unwrap();
+/// // Run `flatpak update` in the terminal
+/// let updates = check_flatpak_updates_online().await.unwrap();
+/// assert!(updates.is_empty());
+/// # };
+/// ```
+pub async fn check_flatpak_updates_online() -> Result<Vec<FlatpakUpdate>> {
+    let output = Command::new("flatpak")
+        .args([
+            "remote-ls",
+            "--updates",
+            "--columns=application,branch,origin",
+        ])
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::FlatpakReturnedError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    parse_flatpak_output(&output.stdout)
+}
+
+/// Helper function - parse the tab separated output of `flatpak remote-ls`.
+fn parse_flatpak_output(output: &[u8]) -> Result<Vec<FlatpakUpdate>> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(parse_flatpak_update)
+        .collect()
+}
+
+fn parse_flatpak_update(line: &str) -> Result<FlatpakUpdate> {
+    let mut fields = line.split('\t').map(str::trim);
+    let (Some(app_id), Some(branch), Some(remote), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err(Error::ParseErrorFlatpak(line.to_string()));
+    };
+    Ok(FlatpakUpdate {
+        app_id: app_id.to_string(),
+        branch: branch.to_string(),
+        remote: remote.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_flatpak_output, FlatpakUpdate};
+    use crate::Error;
+
+    #[test]
+    fn test_parse_flatpak_output() {
+        let output = b"org.mozilla.firefox\tstable\tflathub\norg.gnome.Platform\t47\tflathub\n\n";
+        let expected = vec![
+            FlatpakUpdate {
+                app_id: "org.mozilla.firefox".to_string(),
+                branch: "stable".to_string(),
+                remote: "flathub".to_string(),
+            },
+            FlatpakUpdate {
+                app_id: "org.gnome.Platform".to_string(),
+                branch: "47".to_string(),
+                remote: "flathub".to_string(),
+            },
+        ];
+        assert_eq!(parse_flatpak_output(output).unwrap(), expected);
+    }
+    #[test]
+    fn test_parse_flatpak_output_invalid() {
+        match parse_flatpak_output(b"org.mozilla.firefox stable flathub").unwrap_err() {
+            Error::ParseErrorFlatpak(line) => {
+                assert_eq!(line, "org.mozilla.firefox stable flathub")
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Machine-readable JSON output of update check results, for use in scripts or
+//! other status bars (e.g. waybar/polybar).
+//!
+//! # Schema
+//! The output of [`to_json`] is an object with one entry per update source,
+//! plus a total:
+//!```json
+//! {
+//!   "pacman": {
+//!     "count": 1,
+//!     "updates": [
+//!       {
+//!         "pkgname": "libadwaita",
+//!         "pkgver_cur": "1:1.6.0",
+//!         "pkgrel_cur": "1",
+//!         "pkgver_new": "1:1.6.1",
+//!         "pkgrel_new": "1",
+//!         "hold": false
+//!       }
+//!     ],
+//!     "error": null
+//!   },
+//!   "aur": {
+//!     "count": 1,
+//!     "updates": [
+//!       {
+//!         "pkgname": "paru",
+//!         "pkgver_cur": "2.0.3",
+//!         "pkgrel_cur": "1",
+//!         "pkgver_new": "2.0.4",
+//!         "pkgrel_new": "1",
+//!         "flagged_out_of_date": null,
+//!         "pkgbase": "paru"
+//!       }
+//!     ],
+//!     "error": null
+//!   },
+//!   "devel": {
+//!     "count": 1,
+//!     "updates": [
+//!       {
+//!         "pkgname": "hyprlang-git",
+//!         "pkgver_cur": "0.6.0.r2.g1a2b3c4",
+//!         "pkgrel_cur": "1",
+//!         "ref_id_new": "7a8b9c0",
+//!         "ref_id_full": "7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b",
+//!         "remote_commit_date": "2024-12-01T10:00:00+10:00",
+//!         "checked_at": "2024-12-02T01:00:00Z",
+//!         "pkgbase": "hyprlang-git"
+//!       }
+//!     ],
+//!     "error": null
+//!   },
+//!   "total": 3
+//! }
+//! ```
+//! - `count` is the number of entries in `updates`.
+//! - If checking a source failed, its `count` is 0, `updates` is empty and
+//!   `error` contains the error message. Otherwise `error` is `null`.
+//! - `total` is the sum of all `count`s.
+//! - `hold` is true if the package is listed in `HoldPkg` in pacman.conf.
+//! - `flagged_out_of_date` is the time the package was flagged out-of-date on
+//!   the AUR, or `null` if it isn't flagged.
+//! - `checked_at` is when the source repo was last checked online.
+//! - `pkgbase` is the AUR pkgbase the package is built from, which differs from
+//!   `pkgname` for split packages. It can be `null` if unknown.
+//! - `remote_commit_date` is `null` unless the devel updates were checked with
+//!   `check_devel_updates_online_with_dates()`.
+//!
+//! Fields may be added in future versions, but existing fields will not be
+//! removed or renamed.
+use crate::{AllUpdates, AurUpdate, DevelUpdate, Result, Update};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    pacman: JsonSource<'a, Update>,
+    aur: JsonSource<'a, AurUpdate>,
+    devel: JsonSource<'a, DevelUpdate>,
+    total: usize,
+}
+
+#[derive(Serialize)]
+struct JsonSource<'a, T> {
+    count: usize,
+    updates: &'a [T],
+    error: Option<String>,
+}
+
+impl<'a, T> From<&'a Result<Vec<T>>> for JsonSource<'a, T> {
+    fn from(value: &'a Result<Vec<T>>) -> Self {
+        match value {
+            Ok(updates) => JsonSource {
+                count: updates.len(),
+                updates,
+                error: None,
+            },
+            Err(e) => JsonSource {
+                count: 0,
+                updates: &[],
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Serialize the combined results of the pacman, aur and devel checks into a
+/// JSON string, following the schema documented in this module.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// let (updates, _) = check_all_updates_online().await;
+/// println!("{}", json::to_json(&updates).unwrap());
+/// # };
+/// ```
+pub fn to_json(updates: &AllUpdates) -> serde_json::Result<String> {
+    let AllUpdates {
+        pacman, aur, devel, ..
+    } = updates;
+    let pacman = JsonSource::from(pacman);
+    let aur = JsonSource::from(aur);
+    let devel = JsonSource::from(devel);
+    let total = pacman.count + aur.count + devel.count;
+    serde_json::to_string(&JsonOutput {
+        pacman,
+        aur,
+        devel,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_json;
+    use crate::{AllUpdates, Error, Update};
+
+    #[test]
+    fn test_to_json() {
+        let updates = AllUpdates {
+            pacman: Ok(vec![Update {
+                pkgname: "libadwaita".to_string(),
+                pkgver_cur: "1:1.6.0".to_string(),
+                pkgrel_cur: "1".to_string(),
+                pkgver_new: "1:1.6.1".to_string(),
+                pkgrel_new: "1".to_string(),
+                hold: false,
+            }]),
+            aur: Err(Error::GetAurPackageFailed(None)),
+            devel: Ok(vec![]),
+            warnings: Vec::new(),
+        };
+        let actual: serde_json::Value = serde_json::from_str(&to_json(&updates).unwrap()).unwrap();
+        let expected = serde_json::json!({
+            "pacman": {
+                "count": 1,
+                "updates": [{
+                    "pkgname": "libadwaita",
+                    "pkgver_cur": "1:1.6.0",
+                    "pkgrel_cur": "1",
+                    "pkgver_new": "1:1.6.1",
+                    "pkgrel_new": "1",
+                    "hold": false
+                }],
+                "error": null
+            },
+            "aur": {
+                "count": 0,
+                "updates": [],
+                "error": "Failed to get package from AUR `None`"
+            },
+            "devel": { "count": 0, "updates": [], "error": null },
+            "total": 1
+        });
+        assert_eq!(actual, expected);
+    }
+}
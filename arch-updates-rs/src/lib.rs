@@ -2,6 +2,23 @@
 //! Library to query arch linux packaging tools to see if updates are available.
 //! Designed for cosmic-applet-arch, but could be used in similar apps as well.
 //!
+//! # Features
+//! - `aur` (default) - AUR and devel package checks. Without this feature only
+//!   the pacman checks are available, and the web dependencies aren't built.
+//! - `serde` - `Serialize` implementations and the [`json`] module.
+//! - `flatpak` - check for flatpak updates using `flatpak remote-ls`, for
+//!   systems that use flatpak alongside pacman.
+//!
+//! # Cancellation
+//! All checks are cancellation safe - if a check future is dropped (e.g. by
+//! `tokio::time::timeout`), any child processes it spawned are killed.
+//!
+//! # Offline checks
+//! The `_offline` functions never use the network. They only run local
+//! commands (`pacman`, `pacman-conf` and `checkupdates --nosync`), and compare
+//! the result against the cache returned by an earlier online check. No HTTP
+//! client is created, so they can be used in tools that must stay offline.
+//!
 //! # Usage example
 //! This example shows how to check for updates online and print them to the
 //! terminal. It also shows how to check for updates offline, using the cache
@@ -40,20 +57,42 @@
 //! }
 //! ```
 use core::str;
-use futures::{stream::FuturesOrdered, StreamExt, TryStreamExt};
-use raur::Raur;
-use srcinfo::Srcinfo;
+use rustix::fs::FlockOperation;
 use std::{
+    fs::File,
     io,
-    str::{FromStr, Utf8Error},
+    path::{Path, PathBuf},
+    process::Output,
+    str::Utf8Error,
+    time::SystemTime,
 };
 use thiserror::Error;
 use tokio::process::Command;
-use version_compare::Version;
 
-/// Packages ending with one of the devel suffixes will be checked against the
-/// repository, as well as just the pkgver and pkgrel.
-pub const DEVEL_SUFFIXES: [&str; 1] = ["-git"];
+#[cfg(feature = "aur")]
+mod aur;
+#[cfg(feature = "aur")]
+mod checker;
+#[cfg(feature = "flatpak")]
+mod flatpak;
+#[cfg(all(feature = "serde", feature = "aur"))]
+pub mod json;
+pub mod pacman_conf;
+
+#[cfg(feature = "aur")]
+pub use aur::{
+    check_all_updates_offline, check_all_updates_online, check_all_updates_online_with_progress,
+    check_all_updates_online_with_timings, check_aur_updates_offline, check_aur_updates_online,
+    check_devel_updates_offline, check_devel_updates_online, check_devel_updates_online_with_dates,
+    check_devel_updates_online_with_progress, check_orphaned_aur_packages, check_package_update,
+    check_specified_packages, get_aur_packages, get_devel_packages, get_uncached_aur_packages,
+    AllUpdates, AllUpdatesCache, AurUpdate, CheckTimings, DevelUpdate, Package,
+    PackageUpdateStatus, Progress, UpdateDiff, Warning, DEVEL_SUFFIXES,
+};
+#[cfg(feature = "aur")]
+pub use checker::{UpdateChecker, UpdateCheckerOptions, DEFAULT_HTTP_TIMEOUT, DEFAULT_USER_AGENT};
+#[cfg(feature = "flatpak")]
+pub use flatpak::{check_flatpak_updates_online, FlatpakUpdate};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -61,6 +100,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     #[error("IO error running command `{0}`")]
     Io(#[from] io::Error),
+    #[cfg(feature = "aur")]
     #[error("Web error `{0}`")]
     Web(#[from] reqwest::Error),
     #[error("Error parsing stdout from command")]
@@ -69,56 +109,64 @@ pub enum Error {
     GetIgnoredPackagesFailed,
     #[error("Head identifier too short")]
     HeadIdentifierTooShort,
+    #[error("Ref `{0}` not found in git ls-remote output")]
+    RefNotFound(String),
     #[error("Failed to get package from AUR `{0:?}`")]
     /// # Note
     /// Due to the API design, it's not always possible to know the name of the
     /// aur package we failed to get.
     GetAurPackageFailed(Option<String>),
+    #[cfg(feature = "aur")]
     #[error("Error parsing .SRCINFO")]
     ParseErrorSrcinfo(#[from] srcinfo::Error),
+    #[error("checkupdates returned an error: `{0}`")]
+    CheckUpdatesReturnedError(String),
     #[error("Failed to parse update from checkupdates string: `{0}`")]
     ParseErrorCheckUpdates(String),
     #[error("Failed to parse update from pacman string: `{0}`")]
     ParseErrorPacman(String),
     #[error("Failed to parse pkgver and pkgrel from string `{0}`")]
     ParseErrorPkgverPkgrel(String),
+    #[error("Failed to parse pacman.conf: {0}")]
+    ParseErrorPacmanConf(String),
+    #[error("Failed to parse commit date from string `{0}`")]
+    ParseErrorCommitDate(String),
+    #[error("Failed to parse boot time from /proc/stat")]
+    ParseErrorBootTime,
+    #[error("No cache available from a previous online check")]
+    NoCache,
+    #[cfg(feature = "aur")]
+    #[error("Not checked, as the update checker is offline only")]
+    /// The check needs the network, but `UpdateCheckerOptions::offline_only`
+    /// is set.
+    OfflineOnly,
+    #[error("flatpak returned an error: `{0}`")]
+    FlatpakReturnedError(String),
+    #[error("Failed to parse update from flatpak string: `{0}`")]
+    ParseErrorFlatpak(String),
 }
 
-/// Current status of an installed pacman or AUR package, vs the status of the
-/// latest version.
+/// Current status of an installed pacman package, vs the status of the latest
+/// version.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Update {
     pub pkgname: String,
     pub pkgver_cur: String,
     pub pkgrel_cur: String,
     pub pkgver_new: String,
     pub pkgrel_new: String,
+    /// True if the package is listed in `HoldPkg` in pacman.conf. These are
+    /// updated as normal, but generally warrant extra attention.
+    pub hold: bool,
 }
 
-/// Current status of an installed devel package, vs latest commit hash on the
-/// source repo.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct DevelUpdate {
-    pub pkgname: String,
-    pub pkgver_cur: String,
-    pub pkgrel_cur: String,
-    /// When checking a devel update, we don't get a pkgver/pkgrel so-to-speak,
-    /// we instead get the github ref.
-    pub ref_id_new: String,
-}
-
-#[derive(Clone, Debug, Eq, PartialEq)]
-struct Package {
-    pub pkgname: String,
-    pub pkgver: String,
-    pub pkgrel: String,
-}
-
-#[derive(Clone, Debug, Eq, PartialEq)]
-struct PackageUrl<'a> {
-    remote: String,
-    protocol: &'a str,
-    branch: Option<&'a str>,
+impl Update {
+    /// True if only the pkgrel has changed, i.e the package has been rebuilt
+    /// without a new upstream version.
+    pub fn is_pkgrel_only(&self) -> bool {
+        self.pkgver_cur == self.pkgver_new
+    }
 }
 
 /// Use the `checkupdates` function to check if any pacman-managed packages have
@@ -126,6 +174,11 @@ struct PackageUrl<'a> {
 ///
 /// Online version - this function uses the network.
 ///
+/// # Notes
+///  - `checkupdates` uses a single temporary sync database per user, so
+///    concurrent runs can clash. To avoid this, an exclusive lock (shared
+///    between processes using this library) is held while `checkupdates` is
+///    running, and concurrent calls will wait for it to be released.
 /// # Usage
 /// ```no_run
 /// # use arch_updates_rs::*;
@@ -136,14 +189,13 @@ struct PackageUrl<'a> {
 /// assert!(updates.is_empty());
 /// # };
 pub async fn check_pacman_updates_online() -> Result<Vec<Update>> {
+    let _lock = lock_checkupdates().await?;
     let output = Command::new("checkupdates")
         .arg("--nocolor")
+        .kill_on_drop(true)
         .output()
         .await?;
-    str::from_utf8(output.stdout.as_slice())?
-        .lines()
-        .map(parse_update)
-        .collect()
+    parse_checkupdates(checkupdates_stdout(&output)?).await
 }
 
 /// Use the `checkupdates` function to check if any pacman-managed packages have
@@ -152,6 +204,9 @@ pub async fn check_pacman_updates_online() -> Result<Vec<Update>> {
 /// Offline version - this function doesn't use the network, it doesn't require
 /// a cache either as `checkupdates` manages its own sync database.
 ///
+/// # Notes
+///  - As per `check_pacman_updates_online()`, an exclusive lock is held while
+///    `checkupdates` is running.
 /// # Usage
 /// ```no_run
 /// # use arch_updates_rs::*;
@@ -164,345 +219,367 @@ pub async fn check_pacman_updates_online() -> Result<Vec<Update>> {
 /// assert!(offline.is_empty());
 /// # };
 pub async fn check_pacman_updates_offline() -> Result<Vec<Update>> {
+    let _lock = lock_checkupdates().await?;
     let output = Command::new("checkupdates")
         .args(["--nosync", "--nocolor"])
+        .kill_on_drop(true)
         .output()
         .await?;
-    str::from_utf8(output.stdout.as_slice())?
+    parse_checkupdates(checkupdates_stdout(&output)?).await
+}
+
+/// Get the stdout of `checkupdates`, or its error message if it failed.
+/// `checkupdates` exits with 0 if updates are available, 2 if there are no
+/// updates, and 1 on failure.
+fn checkupdates_stdout(output: &Output) -> Result<&[u8]> {
+    match output.status.code() {
+        Some(0) | Some(2) => Ok(output.stdout.as_slice()),
+        _ => Err(Error::CheckUpdatesReturnedError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+/// Parse the output of `checkupdates`, marking updates to packages in
+/// `HoldPkg`.
+async fn parse_checkupdates(output: &[u8]) -> Result<Vec<Update>> {
+    let hold_packages = get_hold_packages().await?;
+    parse_checkupdates_output(output, &hold_packages)
+}
+
+/// Helper function - parse the output of `checkupdates`.
+/// Output is decoded lossily, as the fields we parse are ASCII and a single
+/// invalid byte shouldn't fail the whole check.
+fn parse_checkupdates_output(output: &[u8], hold_packages: &[String]) -> Result<Vec<Update>> {
+    String::from_utf8_lossy(output)
         .lines()
-        .map(parse_update)
+        .map(|line| {
+            let mut update = parse_update(line)?;
+            update.hold = hold_packages.contains(&update.pkgname);
+            Ok(update)
+        })
         .collect()
 }
 
-/// Check if any packages ending in `DEVEL_SUFFIXES` have updates to their
-/// source repositories.
-///
-/// Online version - this function checks the network.
-/// Returns a tuple of:
-///  - Packages that are not up to date.
-///  - Latest version of all devel packages - for offline use.
+/// pacman conf has a list of packages that pacman asks for confirmation before
+/// removing (HoldPkg). This command fetches their pkgnames.
+async fn get_hold_packages() -> Result<Vec<String>> {
+    let output = Command::new("pacman-conf")
+        .arg("HoldPkg")
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Check if any pacman-managed packages that are ignored (`IgnorePkg` or
+/// `IgnoreGroup` in pacman.conf) have updates due. These are held back, so are
+/// not returned by `check_pacman_updates_online()` or
+/// `check_pacman_updates_offline()`.
 ///
-/// # Notes
-///  - For this to be accurate, it's reliant on each devel package having only
-///    one source URL. If this is not the case, the function will produce a
-///    DevelUpdate for each source url, and may assume one or more are out of
-///    date.
-///  - This is also reliant on VCS packages being good
-///    citizens and following the VCS Packaging Guidelines.
-///    <https://wiki.archlinux.org/title/VCS_package_guidelines>
+/// This function doesn't use the network, it uses the sync database from the
+/// last `check_pacman_updates_online()`. As it requires an extra call to
+/// pacman, it's not run as part of the other pacman checks - call it only if
+/// the held back updates are required.
 /// # Usage
 /// ```no_run
 /// # use arch_updates_rs::*;
 /// # async {
-/// let (updates, _) = check_devel_updates_online().await.unwrap();
-/// // Run `paru -Syu` in the terminal
-/// let (updates, _) = check_devel_updates_online().await.unwrap();
-/// assert!(updates.is_empty());
+/// check_pacman_updates_online().await.unwrap();
+/// let held_back = check_pacman_ignored_updates().await.unwrap();
 /// # };
-pub async fn check_devel_updates_online() -> Result<(Vec<DevelUpdate>, Vec<DevelUpdate>)> {
-    let devel_packages = get_devel_packages().await?;
-    let devel_updates = futures::stream::iter(devel_packages.into_iter())
-        .then(|pkg| async move {
-            let updates = get_aur_srcinfo(&pkg.pkgname)
-                .await?
-                .base
-                .source
-                .into_iter()
-                .flat_map(move |arch| arch.vec.into_iter())
-                .filter_map(|url| {
-                    let url = parse_url(&url)?;
-                    let PackageUrl { remote, branch, .. } = url;
-                    // This allocation isn't ideal, but it's here to work around lifetime issues
-                    // with nested streams that I've been unable to resolve. Spent a few hours on it
-                    // so far!
-                    Some((remote, branch.map(ToString::to_string)))
-                })
-                .map(move |(remote, branch)| {
-                    let pkgver_cur = pkg.pkgver.to_owned();
-                    let pkgrel_cur = pkg.pkgrel.to_owned();
-                    let pkgname = pkg.pkgname.to_owned();
-                    async move {
-                        let ref_id_new = get_head_identifier(remote, branch.as_deref()).await?;
-                        Ok::<_, crate::Error>(DevelUpdate {
-                            pkgname,
-                            pkgver_cur,
-                            ref_id_new,
-                            pkgrel_cur,
-                        })
-                    }
-                })
-                .collect::<FuturesOrdered<_>>();
-            Ok::<_, Error>(updates)
-        })
-        .try_flatten()
-        .try_collect::<Vec<_>>()
+pub async fn check_pacman_ignored_updates() -> Result<Vec<Update>> {
+    let _lock = lock_checkupdates().await?;
+    let output = Command::new("pacman")
+        .arg("-Qu")
+        .arg("--dbpath")
+        .arg(checkupdates_db_path())
+        .args(["--logfile", "/dev/null", "--color", "never"])
+        .kill_on_drop(true)
+        .output()
         .await?;
-    Ok((
-        devel_updates
-            .iter()
-            .filter(|update| devel_update_due(update))
-            .cloned()
-            .collect::<Vec<_>>(),
-        devel_updates,
-    ))
+    parse_ignored_updates(&String::from_utf8_lossy(&output.stdout))
 }
 
-/// Check if any packages ending in `DEVEL_SUFFIXES` have updates to their
-/// source repositories.
+/// Get the time the `checkupdates` sync database was last synced, i.e the last
+/// time `check_pacman_updates_online()` (or `checkupdates` itself) was run.
 ///
-/// Offline version - this function needs a reference to the latest version of
-/// all devel packages (returned from `check_devel_updates_online()`.
+/// The offline check only reflects the state of the repositories as at this
+/// time, so this can be used to tell how fresh its results are.
+/// Returns `None` if the database has never been synced.
 /// # Usage
 /// ```no_run
 /// # use arch_updates_rs::*;
 /// # async {
-/// let (online, cache) = check_devel_updates_online().await.unwrap();
-/// let offline = check_devel_updates_offline(&cache).await.unwrap();
-/// assert_eq!(online, offline);
-/// // Run `paru -Syu` in the terminal
-/// let offline = check_devel_updates_offline(&cache).await.unwrap();
-/// assert!(offline.is_empty());
+/// check_pacman_updates_online().await.unwrap();
+/// let last_synced = get_pacman_db_last_synced().await.unwrap();
+/// assert!(last_synced.is_some());
 /// # };
-pub async fn check_devel_updates_offline(cache: &[DevelUpdate]) -> Result<Vec<DevelUpdate>> {
-    let devel_packages = get_devel_packages().await?;
-    let devel_updates = devel_packages
-        .iter()
-        .flat_map(|package| {
-            cache
-                .iter()
-                .filter(|cache_package| cache_package.pkgname == package.pkgname)
-                .map(move |cache_package| DevelUpdate {
-                    pkgname: package.pkgname.to_owned(),
-                    pkgver_cur: package.pkgver.to_owned(),
-                    pkgrel_cur: package.pkgrel.to_owned(),
-                    ref_id_new: cache_package.ref_id_new.to_owned(),
-                })
-        })
-        .filter(devel_update_due)
-        .collect();
-    Ok(devel_updates)
+pub async fn get_pacman_db_last_synced() -> Result<Option<SystemTime>> {
+    let mut entries = match tokio::fs::read_dir(checkupdates_db_path().join("sync")).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut last_synced = None;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.path().extension().map_or(true, |ext| ext != "db") {
+            continue;
+        }
+        let modified = entry.metadata().await?.modified()?;
+        last_synced = last_synced.max(Some(modified));
+    }
+    Ok(last_synced)
 }
 
-/// Check if any AUR packages have updates to their pkgver-pkgrel.
-///
-/// Online version - this function checks the network.
-/// Returns a tuple of:
-///  - Packages that are not up to date.
-///  - Latest version of all aur packages - for offline use.
+/// Directory containing the modules for each installed kernel.
+const KERNEL_MODULES_PATH: &str = "/usr/lib/modules";
+
+/// Log of all transactions run by pacman.
+const PACMAN_LOG_PATH: &str = "/var/log/pacman.log";
+
+/// Whether a reboot is recommended to finish applying installed updates, from
+/// `check_reboot_recommended()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RebootRecommendation {
+    /// Release of the running kernel, as per `uname -r`.
+    pub running_kernel: String,
+    /// True if the running kernel's modules are no longer installed, i.e the
+    /// kernel has been upgraded since boot. Until a reboot, modules that
+    /// haven't been loaded yet (e.g for newly plugged in devices) will fail to
+    /// load.
+    pub kernel_updated: bool,
+    /// Packages upgraded since boot that running processes keep using until a
+    /// reboot - `systemd`, `glibc`, and `linux*` (kernels and firmware), as
+    /// per the pacman log.
+    pub core_packages_updated: Vec<String>,
+}
+
+impl RebootRecommendation {
+    /// True if a reboot is recommended.
+    pub fn is_recommended(&self) -> bool {
+        self.kernel_updated || !self.core_packages_updated.is_empty()
+    }
+}
+
+/// Check if a reboot is recommended, e.g because the kernel or systemd has
+/// been upgraded since boot.
 ///
-/// # Notes
-///  - Locally installed packages that aren't in the AUR are currently not
-///    implemented and may return an error.
+/// This doesn't use the network.
 /// # Usage
 /// ```no_run
 /// # use arch_updates_rs::*;
 /// # async {
-/// let (updates, _) = check_aur_updates_online().await.unwrap();
-/// // Run `paru -Syu` in the terminal
-/// let (updates, _) = check_aur_updates_online().await.unwrap();
-/// assert!(updates.is_empty());
+/// if check_reboot_recommended().await.unwrap().is_recommended() {
+///     println!("Reboot to finish applying updates");
+/// }
 /// # };
-pub async fn check_aur_updates_online() -> Result<(Vec<Update>, Vec<Update>)> {
-    let old = get_aur_packages().await?;
-    let aur = raur::Handle::new();
-    let cache: Vec<Update> = aur
-        .info(
-            old.iter()
-                .map(|pkg| pkg.pkgname.to_owned())
-                .collect::<Vec<_>>()
-                .as_slice(),
-        )
-        .await
-        .map_err(|_| Error::GetAurPackageFailed(None))?
-        .into_iter()
-        .filter_map(|new| {
-            let matching_old = &old.iter().find(|old| old.pkgname == new.name)?.clone();
-            let (pkgver_new, pkgrel_new) = parse_ver_and_rel(new.version).unwrap();
-            Some(Update {
-                pkgname: matching_old.pkgname.to_owned(),
-                pkgver_cur: matching_old.pkgver.to_owned(),
-                pkgrel_cur: matching_old.pkgrel.to_owned(),
-                pkgver_new,
-                pkgrel_new,
-            })
-        })
-        .collect();
-    Ok((
-        cache
-            .iter()
-            .filter(|update| aur_update_due(update))
-            .cloned()
-            .collect(),
-        cache,
-    ))
+pub async fn check_reboot_recommended() -> Result<RebootRecommendation> {
+    let running_kernel = tokio::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .await?
+        .trim()
+        .to_string();
+    // Each installed kernel has a modules directory named after its release,
+    // which pacman removes when the kernel is upgraded.
+    let kernel_updated =
+        !tokio::fs::try_exists(Path::new(KERNEL_MODULES_PATH).join(&running_kernel)).await?;
+    let boot_time = parse_boot_time(&tokio::fs::read_to_string("/proc/stat").await?)?;
+    let core_packages_updated = match tokio::fs::read(PACMAN_LOG_PATH).await {
+        Ok(log) => parse_core_upgrades_since(&String::from_utf8_lossy(&log), boot_time),
+        // Nothing has been upgraded if pacman has never logged a transaction.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(RebootRecommendation {
+        running_kernel,
+        kernel_updated,
+        core_packages_updated,
+    })
 }
 
-/// Check if any AUR packages have updates to their pkgver-pkgrel.
+/// Parse the boot time (in seconds since the unix epoch) from the `btime` line
+/// of `/proc/stat`.
+/// Example input: "btime 1733011200"
+fn parse_boot_time(proc_stat: &str) -> Result<i64> {
+    proc_stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|btime| btime.trim().parse().ok())
+        .ok_or(Error::ParseErrorBootTime)
+}
+
+/// True if running processes keep using `pkgname` after it's upgraded, until a
+/// reboot.
+fn is_core_package(pkgname: &str) -> bool {
+    pkgname == "systemd" || pkgname == "glibc" || pkgname.starts_with("linux")
+}
+
+/// Get the core packages (as per `is_core_package()`) upgraded at or after
+/// `boot_time` (in seconds since the unix epoch) from the pacman log, each only
+/// once.
+/// Example input: "[2024-12-01T10:02:42+1000] [ALPM] upgraded systemd (256.8-1
+/// -> 257-1)"
 ///
-/// Offline version - this function needs a reference to the latest version of
-/// all aur packages (returned from `check_aur_updates_online()`.
-/// # Usage
-/// ```no_run
-/// # use arch_updates_rs::*;
-/// # async {
-/// let (online, cache) = check_aur_updates_online().await.unwrap();
-/// let offline = check_aur_updates_offline(&cache).await.unwrap();
-/// assert_eq!(online, offline);
-/// // Run `paru -Syu` in the terminal
-/// let offline = check_aur_updates_offline(&cache).await.unwrap();
-/// assert!(offline.is_empty());
-/// # };
-pub async fn check_aur_updates_offline(cache: &[Update]) -> Result<Vec<Update>> {
-    let old = get_aur_packages().await?;
-    let updates = old
-        .iter()
-        .map(|old_package| {
-            let matching_cached = cache
-                .iter()
-                .find(|cache_package| cache_package.pkgname == old_package.pkgname);
-            let (pkgver_new, pkgrel_new) = match matching_cached {
-                Some(cache_package) => (
-                    cache_package.pkgver_new.to_owned(),
-                    cache_package.pkgrel_new.to_owned(),
-                ),
-                None => (old_package.pkgver.to_owned(), old_package.pkgrel.to_owned()),
-            };
-            Update {
-                pkgname: old_package.pkgname.to_owned(),
-                pkgver_cur: old_package.pkgver.to_owned(),
-                pkgrel_cur: old_package.pkgrel.to_owned(),
-                pkgver_new,
-                pkgrel_new,
-            }
-        })
-        .filter(aur_update_due)
-        .collect();
-    Ok(updates)
+/// Lines in the older log format, with no time zone, can't be compared against
+/// the boot time and are skipped - they predate pacman 5.2, so can't be from
+/// the current boot on an up to date system.
+fn parse_core_upgrades_since(log: &str, boot_time: i64) -> Vec<String> {
+    let mut packages = Vec::new();
+    for line in log.lines() {
+        let Some((timestamp, upgrade)) = line
+            .strip_prefix('[')
+            .and_then(|line| line.split_once("] [ALPM] upgraded "))
+        else {
+            continue;
+        };
+        let Some(pkgname) = upgrade.split_whitespace().next() else {
+            continue;
+        };
+        if is_core_package(pkgname)
+            && parse_log_timestamp(timestamp).is_some_and(|time| time >= boot_time)
+            && !packages.iter().any(|package| package == pkgname)
+        {
+            packages.push(pkgname.to_string());
+        }
+    }
+    packages
 }
 
-/// Returns true if a DevelUpdate is due.
-fn devel_update_due(update: &DevelUpdate) -> bool {
-    !update.pkgver_cur.contains(&update.ref_id_new)
+/// Parse a pacman log timestamp into seconds since the unix epoch.
+/// Example input: "2024-12-01T10:02:42+1000"
+fn parse_log_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.split_once('T')?;
+    let (time, offset) = time.split_at(time.find(['+', '-'])?);
+    let [year, month, day] = parse_fields(date, '-')?;
+    let [hour, minute, second] = parse_fields(time, ':')?;
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let offset_hours = offset.get(1..3)?.parse::<i64>().ok()?;
+    let offset_minutes = offset.get(3..5)?.parse::<i64>().ok()?;
+    Some(
+        days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+            - sign * (offset_hours * 3600 + offset_minutes * 60),
+    )
 }
 
-/// Return true if an aur package is due for an update.
-fn aur_update_due(package: &Update) -> bool {
-    // If it's not possible to determine ordering for a package, it will be filtered
-    // out. Note that this can include some VCS packages using
-    // commit hashes as pkgver. That is likely acceptable behaviour
-    // as VCS packages will be analyzed in check_devel_updates().
-    let Some(pkgver_new) = Version::from(&package.pkgver_new) else {
-        return false;
-    };
-    let Some(pkgver_old) = Version::from(&package.pkgver_cur) else {
-        return false;
-    };
-    pkgver_new > pkgver_old || (pkgver_new == pkgver_old && package.pkgrel_new > package.pkgrel_cur)
+/// Helper function - parse exactly 3 integers separated by `separator`.
+fn parse_fields(s: &str, separator: char) -> Option<[i64; 3]> {
+    let mut fields = s.split(separator).map(|field| field.parse().ok());
+    let parsed = [fields.next()??, fields.next()??, fields.next()??];
+    fields.next().is_none().then_some(parsed)
 }
 
-/// pacman conf has a list of packages that should be ignored by pacman. This
-/// command fetches their pkgnames.
-async fn get_ignored_packages() -> Result<Vec<String>> {
-    // I considered pacmanconf crate here, but it's sync, and does the same thing
-    // under the hood (runs pacman-conf) as a Command.
-    let output = Command::new("pacman-conf")
-        .arg("IgnorePkg")
-        .output()
-        .await?;
-    Ok(str::from_utf8(output.stdout.as_slice())
-        .map_err(|_| Error::GetIgnoredPackagesFailed)?
-        .lines()
-        .map(ToString::to_string)
-        .collect())
+/// Days since the unix epoch of a date in the (proleptic) Gregorian calendar.
+/// From <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
 }
 
-/// Get a list of all aur packages on the system.
-/// An AUR package is a package returned by `pacman -Qm` excluding ignored
-/// packages.
-async fn get_aur_packages() -> Result<Vec<Package>> {
-    let (ignored_packages, output) = futures::join!(
-        get_ignored_packages(),
-        Command::new("pacman").arg("-Qm").output()
-    );
-    let ignored_packages = ignored_packages?;
-    str::from_utf8(output?.stdout.as_slice())
-        .map_err(|_| Error::GetIgnoredPackagesFailed)?
-        .lines()
-        // Filter out any ignored packages
-        .filter(|line| {
-            !ignored_packages
-                .iter()
-                .any(|ignored_package| line.contains(ignored_package))
-        })
-        .map(parse_pacman_qm)
-        .collect()
+/// Whether the system can support update checks, from `preflight()`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PreflightReport {
+    /// `checkupdates` (from `pacman-contrib`) is on `PATH`. This is required
+    /// for the pacman checks.
+    pub checkupdates_installed: bool,
+    /// `git` is on `PATH`. This is required for the devel checks.
+    pub git_installed: bool,
+    /// The directory `checkupdates` is locked in is writable.
+    pub lock_dir_writable: bool,
 }
 
-/// Get a list of all devel packages on the system.
-/// A devel package is an AUR package ending with one of the `DEVEL_SUFFIXES`.
-async fn get_devel_packages() -> Result<Vec<Package>> {
-    let aur_packages = get_aur_packages().await?;
-    Ok(aur_packages
-        .into_iter()
-        .filter(|package| {
-            DEVEL_SUFFIXES
-                .iter()
-                .any(|suffix| package.pkgname.to_lowercase().contains(suffix))
-        })
-        .collect())
+impl PreflightReport {
+    /// True if all checks passed.
+    pub fn is_ok(&self) -> bool {
+        self.checkupdates_installed && self.git_installed && self.lock_dir_writable
+    }
+}
+
+/// Check that the programs and directories the update checks rely on are
+/// available, e.g to show a clear message on first run rather than a generic
+/// error.
+///
+/// This doesn't use the network.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::*;
+/// # async {
+/// if !preflight().await.checkupdates_installed {
+///     println!("Install pacman-contrib to check for updates");
+/// }
+/// # };
+pub async fn preflight() -> PreflightReport {
+    tokio::task::spawn_blocking(|| PreflightReport {
+        checkupdates_installed: in_path("checkupdates"),
+        git_installed: in_path("git"),
+        lock_dir_writable: rustix::fs::access(
+            lock_checkupdates_path().parent().unwrap_or(Path::new("/")),
+            rustix::fs::Access::WRITE_OK,
+        )
+        .is_ok(),
+    })
+    .await
+    .unwrap_or(PreflightReport {
+        checkupdates_installed: false,
+        git_installed: false,
+        lock_dir_writable: false,
+    })
 }
 
-/// Get and parse the .SRCINFO for an aur package.
-async fn get_aur_srcinfo(pkgname: &str) -> Result<Srcinfo> {
-    // First we need to get the base repository from the AUR API. Since the pkgname
-    // may not be the same as the repository name (and repository can contain
-    // multiple packages).
-    let aur = raur::Handle::new();
-    let info = &aur
-        .info(&[&pkgname])
-        .await
-        .map_err(|_| Error::GetAurPackageFailed(Some(pkgname.to_string())))?[0];
-    let base = &info.package_base;
-
-    let url = format!("https://aur.archlinux.org/cgit/aur.git/plain/.SRCINFO?h={base}");
-    let raw = reqwest::get(url).await?.text().await?;
-    // The pkg.pkgname field of the .SRCINO is not likely to be populated, but we'll
-    // need it for later parsing, so we populate it ourself.
-    let mut srcinfo = Srcinfo::from_str(&raw)?;
-    srcinfo.pkg.pkgname = pkgname.to_string();
-
-    Ok(srcinfo)
+/// Helper function - true if an executable called `program` is on `PATH`.
+fn in_path(program: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| {
+        rustix::fs::access(dir.join(program).as_path(), rustix::fs::Access::EXEC_OK).is_ok()
+    })
 }
 
-/// Get head identifier for a git repo - last 7 digits from commit hash.
-/// If a branch is not provided, HEAD will be selected.
-async fn get_head_identifier(url: String, branch: Option<&str>) -> Result<String> {
-    let id = str::from_utf8(
-        Command::new("git")
-            .args(["ls-remote", &url, branch.unwrap_or("HEAD")])
-            .output()
-            .await?
-            .stdout
-            .as_ref(),
-    )?
-    .get(0..7)
-    .ok_or_else(|| Error::HeadIdentifierTooShort)?
-    .to_string();
-    Ok(id)
+/// Location of the temporary sync database used by `checkupdates`. This
+/// mirrors the default in the `checkupdates` script, which can be overridden
+/// using the `CHECKUPDATES_DB` environment variable.
+fn checkupdates_db_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("CHECKUPDATES_DB") {
+        return path.into();
+    }
+    std::env::var_os("TMPDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join(format!("checkup-db-{}", rustix::process::getuid().as_raw()))
 }
 
-/// Parse output of pacman -Qm into a package.
-/// Example input: "watchman-bin 2024.04.15.00-1"
-fn parse_pacman_qm(line: &str) -> Result<Package> {
-    let (pkgname, rest) = line
-        .split_once(' ')
-        .ok_or_else(|| Error::ParseErrorPacman(line.to_string()))?;
-    let (pkgver, pkgrel) = parse_ver_and_rel(rest)?;
-    Ok(Package {
-        pkgname: pkgname.to_owned(),
-        pkgver,
-        pkgrel,
+/// Acquire an exclusive lock that should be held while running `checkupdates`.
+/// The lock is a file lock, so it is shared with other processes using this
+/// library. The lock is released when the returned file is dropped.
+async fn lock_checkupdates() -> Result<File> {
+    let path = lock_checkupdates_path();
+    // flock blocks until the lock is available, so it's run on the blocking
+    // threadpool.
+    tokio::task::spawn_blocking(move || {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        rustix::fs::flock(&file, FlockOperation::LockExclusive).map_err(io::Error::from)?;
+        Ok(file)
     })
+    .await
+    .map_err(io::Error::other)?
+}
+
+fn lock_checkupdates_path() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "arch-updates-rs-checkupdates-{}.lock",
+        rustix::process::getuid().as_raw()
+    ))
 }
 
 /// Parse output of a combined pkgrel-pkgver.
@@ -515,6 +592,16 @@ fn parse_ver_and_rel(version: impl AsRef<str>) -> Result<(String, String)> {
     Ok((pkgver.into(), pkgrel.into()))
 }
 
+/// Parse the ignored updates from the output of `pacman -Qu`.
+/// Example input: "linux 6.12.1.arch1-1 -> 6.12.4.arch1-1 [ignored]"
+fn parse_ignored_updates(output: &str) -> Result<Vec<Update>> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_suffix(" [ignored]"))
+        .map(parse_update)
+        .collect()
+}
+
 /// Parse output line from checkupdates
 /// Example input: libadwaita 1:1.6.0-1 -> 1:1.6.1-1
 fn parse_update(value: &str) -> Result<Update> {
@@ -537,57 +624,21 @@ fn parse_update(value: &str) -> Result<Update> {
         pkgrel_cur,
         pkgver_new,
         pkgrel_new,
-    })
-}
-
-/// Parse source field from .SRCINFO
-// NOTE: This is from paru (GPL3)
-fn parse_url(source: &str) -> Option<PackageUrl> {
-    let url = source.splitn(2, "::").last().unwrap();
-
-    if !url.starts_with("git") || !url.contains("://") {
-        return None;
-    }
-
-    let mut split = url.splitn(2, "://");
-    let protocol = split.next().unwrap();
-    let protocol = protocol.rsplit('+').next().unwrap();
-    let rest = split.next().unwrap();
-
-    let mut split = rest.splitn(2, '#');
-    let remote = split.next().unwrap();
-    let remote = remote.split_once('?').map_or(remote, |x| x.0);
-    let remote = format!("{}://{}", protocol, remote);
-
-    let branch = if let Some(fragment) = split.next() {
-        let fragment = fragment.split_once('?').map_or(fragment, |x| x.0);
-        let mut split = fragment.splitn(2, '=');
-        let frag_type = split.next().unwrap();
-
-        match frag_type {
-            "commit" | "tag" => return None,
-            "branch" => split.next(),
-            _ => None,
-        }
-    } else {
-        None
-    };
-
-    Some(PackageUrl {
-        remote,
-        protocol,
-        branch,
+        hold: false,
     })
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        check_aur_updates_offline, check_aur_updates_online, check_devel_updates_offline,
-        check_devel_updates_online, check_pacman_updates_offline, check_pacman_updates_online,
-        get_aur_srcinfo, get_head_identifier, parse_pacman_qm, parse_update, parse_url,
-        parse_ver_and_rel, Error, Package, PackageUrl, Update,
+        check_pacman_updates_offline, check_pacman_updates_online, check_reboot_recommended,
+        checkupdates_stdout, get_pacman_db_last_synced, in_path, lock_checkupdates,
+        parse_boot_time, parse_checkupdates_output, parse_core_upgrades_since,
+        parse_ignored_updates, parse_log_timestamp, parse_update, parse_ver_and_rel, Error,
+        RebootRecommendation, Result, Update,
     };
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
 
     #[tokio::test]
     async fn test_check_pacman_updates() {
@@ -596,60 +647,109 @@ mod tests {
         assert_eq!(online, offline);
     }
     #[tokio::test]
-    async fn test_check_aur_updates() {
-        let (online, cache) = check_aur_updates_online().await.unwrap();
-        let offline = check_aur_updates_offline(&cache).await.unwrap();
-        assert_eq!(online, offline);
-        eprintln!("aur {:#?}", online);
+    async fn test_get_pacman_db_last_synced() {
+        check_pacman_updates_online().await.unwrap();
+        let last_synced = get_pacman_db_last_synced().await.unwrap().unwrap();
+        assert!(last_synced.elapsed().unwrap() < std::time::Duration::from_secs(60));
     }
     #[tokio::test]
-    async fn test_check_devel_updates() {
-        let (online, cache) = check_devel_updates_online().await.unwrap();
-        let offline = check_devel_updates_offline(&cache).await.unwrap();
-        assert_eq!(online, offline);
-        eprintln!("devel {:#?}", online);
+    async fn test_check_reboot_recommended() {
+        let recommendation = check_reboot_recommended().await.unwrap();
+        assert!(!recommendation.running_kernel.is_empty());
     }
-
-    #[tokio::test]
-    async fn test_get_srcinfo() {
-        get_aur_srcinfo("hyprlang-git").await.unwrap();
+    #[test]
+    fn test_parse_boot_time() {
+        let proc_stat = "cpu  1 2 3 4\nintr 5 6\nctxt 7\nbtime 1733011200\nprocesses 8\n";
+        assert_eq!(parse_boot_time(proc_stat).unwrap(), 1733011200);
+        assert!(matches!(
+            parse_boot_time("cpu  1 2 3 4\n"),
+            Err(Error::ParseErrorBootTime)
+        ));
     }
-    #[tokio::test]
-    async fn test_get_url() {
-        let srcinfo = get_aur_srcinfo("hyprlang-git").await.unwrap();
-        let url = srcinfo.base.source.first().unwrap().vec.first().unwrap();
-        parse_url(url).unwrap();
+    #[test]
+    fn test_parse_log_timestamp() {
+        assert_eq!(
+            parse_log_timestamp("2024-12-01T10:00:00+1000"),
+            Some(1733011200)
+        );
+        assert_eq!(
+            parse_log_timestamp("2024-11-30T22:30:00-0130"),
+            Some(1733011200)
+        );
+        assert_eq!(parse_log_timestamp("1970-01-01T00:00:00+0000"), Some(0));
+        // Older log format, with no time zone.
+        assert_eq!(parse_log_timestamp("2019-01-01 10:00"), None);
+        assert_eq!(parse_log_timestamp("2024-12-01T10:00+1000"), None);
+    }
+    #[test]
+    fn test_parse_core_upgrades_since_fixture() {
+        // 2024-12-01T10:00:00+1000, between the two transactions.
+        let boot_time = 1733011200;
+        let log = include_str!("../test_data/pacman_log.txt");
+        assert_eq!(
+            parse_core_upgrades_since(log, boot_time),
+            vec!["systemd".to_string(), "linux-firmware".to_string()]
+        );
+        assert!(parse_core_upgrades_since(log, boot_time + 86400).is_empty());
+    }
+    #[test]
+    fn test_parse_core_upgrades_since_old_format() {
+        let log = "[2019-01-01 10:00] [ALPM] upgraded glibc (2.28-4 -> 2.28-5)\n";
+        assert!(parse_core_upgrades_since(log, 0).is_empty());
+    }
+    #[test]
+    fn test_reboot_recommended() {
+        let recommendation = RebootRecommendation {
+            running_kernel: "6.12.4-arch1-1".to_string(),
+            ..Default::default()
+        };
+        assert!(!recommendation.is_recommended());
+        let recommendation = RebootRecommendation {
+            core_packages_updated: vec!["systemd".to_string()],
+            ..recommendation
+        };
+        assert!(recommendation.is_recommended());
+    }
+    #[test]
+    fn test_in_path() {
+        assert!(in_path("sh"));
+        assert!(!in_path("not-a-real-program"));
     }
     #[tokio::test]
-    async fn test_get_head() {
-        let srcinfo = get_aur_srcinfo("hyprutils-git").await.unwrap();
-        let url = srcinfo.base.source.first().unwrap().vec.first().unwrap();
-        let url_parsed = parse_url(url).unwrap();
-        get_head_identifier(url_parsed.remote, url_parsed.branch)
+    async fn test_lock_checkupdates_exclusive() {
+        let lock = lock_checkupdates().await.unwrap();
+        let timeout = std::time::Duration::from_millis(100);
+        assert!(tokio::time::timeout(timeout, lock_checkupdates())
             .await
-            .unwrap();
+            .is_err());
+        drop(lock);
+        lock_checkupdates().await.unwrap();
     }
 
-    #[test]
-    fn test_parse_url() {
-        let url = parse_url(
-            "paper-icon-theme::git+https://github.com/snwh/paper-icon-theme.git#branch=main",
-        )
-        .unwrap();
-        let expected = PackageUrl {
-            remote: "https://github.com/snwh/paper-icon-theme.git".to_string(),
-            protocol: "https",
-            branch: Some("main"),
-        };
-        assert_eq!(url, expected);
+    fn output(code: i32, stdout: &str, stderr: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
     }
     #[test]
-    fn test_parse_url_none() {
-        let url = parse_url(
-            "paper-icon-themegit:gopher://github.com/snwh/paper-icon-theme.git branch=main",
+    fn test_checkupdates_stdout() {
+        let updates = "libadwaita 1:1.6.0-1 -> 1:1.6.1-2\n";
+        assert_eq!(
+            checkupdates_stdout(&output(0, updates, "")).unwrap(),
+            updates.as_bytes()
         );
-        eprintln!("{:#?}", url);
-        assert!(url.is_none());
+        // 2 means no updates are available.
+        assert!(checkupdates_stdout(&output(2, "", "")).unwrap().is_empty());
+    }
+    #[test]
+    fn test_checkupdates_stdout_error() {
+        let stderr = "==> ERROR: Cannot fetch updates\n";
+        match checkupdates_stdout(&output(1, "", stderr)).unwrap_err() {
+            Error::CheckUpdatesReturnedError(s) => assert_eq!(s, "==> ERROR: Cannot fetch updates"),
+            _ => panic!(),
+        }
     }
     #[test]
     fn test_parse_update() {
@@ -660,6 +760,7 @@ mod tests {
             pkgrel_cur: "1".to_string(),
             pkgver_new: "1:1.6.1".to_string(),
             pkgrel_new: "2".to_string(),
+            hold: false,
         };
         assert_eq!(update, expected);
     }
@@ -674,24 +775,62 @@ mod tests {
         }
     }
     #[test]
-    fn test_parse_pacman_qm() {
-        let update = parse_pacman_qm("winetricks-git 20240105.r47.g72b934e1-2").unwrap();
-        let expected = Package {
-            pkgname: "winetricks-git".to_string(),
-            pkgver: "20240105.r47.g72b934e1".to_string(),
-            pkgrel: "2".to_string(),
+    fn test_parse_ignored_updates() {
+        let output =
+            "libadwaita 1:1.6.0-1 -> 1:1.6.1-2\nlinux 6.12.1.arch1-1 -> 6.12.4.arch1-1 [ignored]";
+        let expected = vec![Update {
+            pkgname: "linux".to_string(),
+            pkgver_cur: "6.12.1.arch1".to_string(),
+            pkgrel_cur: "1".to_string(),
+            pkgver_new: "6.12.4.arch1".to_string(),
+            pkgrel_new: "1".to_string(),
+            hold: false,
+        }];
+        assert_eq!(parse_ignored_updates(output).unwrap(), expected);
+    }
+    #[test]
+    fn test_parse_checkupdates_fixture() {
+        let updates = include_str!("../test_data/checkupdates.txt")
+            .lines()
+            .map(parse_update)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(updates.len(), 4);
+        let expected = Update {
+            pkgname: "glibc".to_string(),
+            pkgver_cur: "2.40+r16+gaa533d58ff".to_string(),
+            pkgrel_cur: "2".to_string(),
+            pkgver_new: "2.40+r66+g7d4b6bcae9".to_string(),
+            pkgrel_new: "1".to_string(),
+            hold: false,
         };
-        assert_eq!(update, expected);
+        assert_eq!(updates[0], expected);
     }
     #[test]
-    fn test_parse_pacman_qm_error() {
-        let str = "winetricks-git0240105.r47.g72b934e1-2";
-        let update = parse_pacman_qm(str).unwrap_err();
-        eprintln!("{:#?}", update);
-        match update {
-            Error::ParseErrorPacman(s) => assert_eq!(s, str),
-            _ => panic!(),
-        }
+    fn test_parse_checkupdates_invalid_utf8() {
+        let output = b"glibc 2.40-2 -> 2.41-1\nlibfoo 1.0\xff-1 -> 1.1-1\n";
+        let updates = parse_checkupdates_output(output, &["glibc".to_string()]).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].pkgname, "glibc");
+        assert!(updates[0].hold);
+        assert_eq!(updates[1].pkgname, "libfoo");
+        assert_eq!(updates[1].pkgver_new, "1.1");
+    }
+    #[test]
+    fn test_parse_ignored_updates_fixture() {
+        let updates = parse_ignored_updates(include_str!("../test_data/pacman_qu.txt")).unwrap();
+        let pkgnames = updates
+            .iter()
+            .map(|update| update.pkgname.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(pkgnames, vec!["linux", "linux-headers"]);
+    }
+    #[test]
+    fn test_is_pkgrel_only() {
+        let update = parse_update("libadwaita 1:1.6.1-1 -> 1:1.6.1-2").unwrap();
+        assert!(update.is_pkgrel_only());
+        let update = parse_update("libadwaita 1:1.6.0-1 -> 1:1.6.1-1").unwrap();
+        assert!(!update.is_pkgrel_only());
     }
     #[test]
     fn test_parse_version() {
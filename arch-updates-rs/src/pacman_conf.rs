@@ -0,0 +1,211 @@
+//! Parser for pacman.conf, to read the ignored packages without running
+//! `pacman-conf` (e.g for use in environments without a subprocess).
+//!
+//! `Include` directives are followed, including wildcards (`*` and `?`) in the
+//! file name component of the included path, as is common with
+//! `/etc/pacman.d/*.conf` style includes.
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Default location of pacman.conf.
+pub const PACMAN_CONF_PATH: &str = "/etc/pacman.conf";
+/// Maximum depth of nested `Include` directives, matching pacman.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Effective `IgnorePkg` and `IgnoreGroup` settings from pacman.conf.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct IgnoreConfig {
+    pub ignore_pkg: Vec<String>,
+    pub ignore_group: Vec<String>,
+}
+
+/// Parse pacman.conf at `path`, following `Include` directives, and return the
+/// effective ignore settings.
+///
+/// Note that this function blocks while reading files.
+/// # Usage
+/// ```no_run
+/// # use arch_updates_rs::pacman_conf::*;
+/// let config = parse_pacman_conf(PACMAN_CONF_PATH).unwrap();
+/// println!("Ignored packages: {:?}", config.ignore_pkg);
+/// ```
+pub fn parse_pacman_conf(path: impl AsRef<Path>) -> Result<IgnoreConfig> {
+    let mut config = IgnoreConfig::default();
+    let mut section = String::new();
+    parse_file(path.as_ref(), &mut section, 0, &mut config)?;
+    Ok(config)
+}
+
+/// Parse a single file into `config`. The current section is shared with
+/// included files, as per pacman.
+fn parse_file(
+    path: &Path,
+    section: &mut String,
+    depth: usize,
+    config: &mut IgnoreConfig,
+) -> Result<()> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(Error::ParseErrorPacmanConf(format!(
+            "Include depth exceeded at {}",
+            path.display()
+        )));
+    }
+    let contents = std::fs::read_to_string(path)?;
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            *section = name.to_string();
+            continue;
+        }
+        // Lines without a value are boolean options e.g `Color`.
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let values = value.split_whitespace().map(ToString::to_string);
+        match key.trim() {
+            "Include" => {
+                for include in expand_include(value.trim())? {
+                    parse_file(&include, section, depth + 1, config)?;
+                }
+            }
+            "IgnorePkg" if section == "options" => config.ignore_pkg.extend(values),
+            "IgnoreGroup" if section == "options" => config.ignore_group.extend(values),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Expand an `Include` path to the files it refers to, in sorted order.
+/// Paths that don't exist are skipped.
+fn expand_include(include: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(include);
+    let (Some(parent), Some(pattern)) = (path.parent(), path.file_name()) else {
+        return Ok(Vec::new());
+    };
+    let pattern = pattern.to_string_lossy();
+    if !pattern.contains(['*', '?']) {
+        return Ok(path
+            .exists()
+            .then(|| path.to_path_buf())
+            .into_iter()
+            .collect());
+    }
+    let entries = match std::fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| wildcard_match(&pattern, &name.to_string_lossy()))
+        {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Match `name` against a pattern containing `*` (any characters) and `?` (one
+/// character) wildcards.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern, and the position in the name it
+    // was matched from, to backtrack to on a mismatch.
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pacman_conf, wildcard_match, IgnoreConfig};
+    use crate::Error;
+
+    #[test]
+    fn test_parse_pacman_conf_include() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("pacman.d");
+        std::fs::create_dir(&conf_d).unwrap();
+        std::fs::write(
+            conf_d.join("ignore.conf"),
+            "IgnorePkg = linux-lts\nIgnoreGroup = gnome\n",
+        )
+        .unwrap();
+        std::fs::write(conf_d.join("other.txt"), "IgnorePkg = not-included\n").unwrap();
+        std::fs::write(conf_d.join("mirrorlist"), "Server = https://example.com\n").unwrap();
+        let main = format!(
+            "[options]\n\
+             # IgnorePkg = commented\n\
+             IgnorePkg = linux nvidia # trailing comment\n\
+             Color\n\
+             Include = {0}/*.conf\n\
+             Include = {0}/missing.conf\n\
+             [core]\n\
+             IgnorePkg = not-options\n\
+             Include = {0}/mirrorlist\n",
+            conf_d.display()
+        );
+        let main_path = dir.path().join("pacman.conf");
+        std::fs::write(&main_path, main).unwrap();
+        let actual = parse_pacman_conf(main_path).unwrap();
+        let expected = IgnoreConfig {
+            ignore_pkg: vec![
+                "linux".to_string(),
+                "nvidia".to_string(),
+                "linux-lts".to_string(),
+            ],
+            ignore_group: vec!["gnome".to_string()],
+        };
+        assert_eq!(actual, expected);
+    }
+    #[test]
+    fn test_parse_pacman_conf_include_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pacman.conf");
+        std::fs::write(&path, format!("Include = {}\n", path.display())).unwrap();
+        match parse_pacman_conf(path).unwrap_err() {
+            Error::ParseErrorPacmanConf(_) => (),
+            _ => panic!(),
+        }
+    }
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.conf", "ignore.conf"));
+        assert!(wildcard_match("a?c*", "abcdef"));
+        assert!(wildcard_match("*a*b", "xaxxab"));
+        assert!(!wildcard_match("*.conf", "ignore.conf.bak"));
+        assert!(!wildcard_match("a?c", "ac"));
+    }
+}
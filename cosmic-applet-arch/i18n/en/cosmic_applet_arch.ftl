@@ -3,8 +3,35 @@ updates-available = { $numberUpdates ->
     [one] 1 { $updateSource } update available
    *[other] { $numberUpdates } { $updateSource } updates available
 }
+source-pacman = pacman
+source-aur = AUR
+source-devel = devel
+source-flatpak = flatpak
 no-updates-available = No updates available.
+panel-per-source = P:{ $pacman } A:{ $aur } D:{ $devel }
+panel-per-source-flatpak = P:{ $pacman } A:{ $aur } D:{ $devel } F:{ $flatpak }
+held-back = { $numberUpdates ->
+    [one] 1 ignored update held back
+   *[other] { $numberUpdates } ignored updates held back
+}
 loading = Loading...
+preflight-no-checkupdates = checkupdates not found - install pacman-contrib to check for updates
+preflight-no-git = git not found - install git to check devel packages
+preflight-lock-dir = Unable to write to the temporary directory
+refreshing = Refreshing...
+refreshing-progress = Refreshing: { $progress }
 last-checked = Last checked: { $dateTime } - Click to refresh
+next-check = Next check in { $minutes } minutes
+sync-database = Sync repo database now
+pause-checks = Pause update checks
+resume-checks = Resume update checks
+db-last-synced = Repo database last synced: { $dateTime }
+not-in-aur = No longer on the AUR: { $packages }
+reboot-recommended = Reboot recommended to finish applying updates
+warning-held-back = Unable to check for held back updates: { $error }
+warning-db-synced = Unable to get repo database sync time: { $error }
+warning-reboot = Unable to check if a reboot is recommended: { $error }
+warning-flatpak = Unable to check for flatpak updates: { $error }
+stale = Last successful check was { $minutes } minutes ago
+online-checks-paused = Online checks paused while on battery
 not-yet = Not yet
-n-more = ...and { $n } more.
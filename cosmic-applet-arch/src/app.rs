@@ -1,14 +1,18 @@
+use crate::config::{Config, MIN_POPUP_HEIGHT};
+use arch_updates_rs::Progress;
 use chrono::{DateTime, Local};
 use cosmic::app::{Core, Task};
 use cosmic::iced::platform_specific::shell::wayland::commands::popup::{destroy_popup, get_popup};
 use cosmic::iced::window::Id;
 use cosmic::iced::Limits;
 use cosmic::{Application, Element};
+use serde::Deserialize;
 use std::sync::Arc;
 use std::time::Duration;
 use subscription::Updates;
 use view::Collapsed;
 
+mod dbus;
 mod subscription;
 mod view;
 
@@ -16,10 +20,29 @@ mod view;
 const INTERVAL: Duration = Duration::from_secs(6);
 /// How long the api call can run without triggering a timeout.
 const TIMEOUT: Duration = Duration::from_secs(60 * 2);
-/// Every `CYCLES` number of `INTERVAL`s (starting at the first interval), the
-/// system will update the latest version in memory from the internet.
-const CYCLES: usize = 600;
+/// How often to update the latest version in memory from the internet
+/// (starting at the first interval). Offline checks are run every `INTERVAL`
+/// in between.
+const ONLINE_CHECK_PERIOD: Duration = Duration::from_secs(60 * 60);
+/// Number of `INTERVAL`s between each online check, derived from
+/// `ONLINE_CHECK_PERIOD` so that changing `INTERVAL` doesn't change how often
+/// the system goes online.
+const CYCLES: usize = {
+    let cycles = (ONLINE_CHECK_PERIOD.as_millis() / INTERVAL.as_millis()) as usize;
+    if cycles == 0 {
+        1
+    } else {
+        cycles
+    }
+};
+/// If an online check fails before any online check has succeeded, it will be
+/// retried after this long, instead of waiting for the next scheduled online
+/// check. The delay doubles after each failed retry, up to `RETRY_BACKOFF_MAX`.
+const RETRY_BACKOFF_MIN: Duration = Duration::from_secs(30);
+const RETRY_BACKOFF_MAX: Duration = Duration::from_secs(60 * 10);
 const SUBSCRIPTION_BUF_SIZE: usize = 10;
+/// User agent sent with the applet's HTTP requests.
+const USER_AGENT: &str = concat!("cosmic-applet-arch/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Default)]
 pub struct CosmicAppletArch {
@@ -31,14 +54,32 @@ pub struct CosmicAppletArch {
     pacman_list_state: Collapsed,
     aur_list_state: Collapsed,
     devel_list_state: Collapsed,
+    held_back_list_state: Collapsed,
+    flatpak_list_state: Collapsed,
     refresh_pressed_notifier: Arc<tokio::sync::Notify>,
     last_checked: Option<DateTime<Local>>,
+    /// When the next scheduled online check is due, as sent by the subscription.
+    next_online_check: Option<DateTime<Local>>,
     error: Option<String>,
+    /// Progress of the current online check, if one is running.
+    progress: Option<Progress>,
+    /// A refresh was requested, and its online check hasn't finished yet.
+    refreshing: bool,
+    /// Scheduled online checks are paused as the system is on battery.
+    online_checks_paused: bool,
+    /// All background checks are paused by the user, until they resume them.
+    /// Sent to the subscription so that it can skip its checks.
+    user_paused: tokio::sync::watch::Sender<bool>,
+    config: Config,
+    /// Update counts published by the D-Bus service.
+    dbus_counts: tokio::sync::watch::Sender<dbus::UpdateCounts>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     ForceGetUpdates,
+    SyncDatabase,
+    TogglePaused,
     TogglePopup,
     ToggleCollapsible(UpdateType),
     PopupClosed(Id),
@@ -47,21 +88,27 @@ pub enum Message {
         checked_online_time: Option<DateTime<Local>>,
     },
     CheckUpdatesErrorsMsg(String),
+    CheckUpdatesProgressMsg(Progress),
+    OnlineChecksPausedMsg(bool),
+    NextOnlineCheckMsg(DateTime<Local>),
+    /// Does nothing, but causes the applet to be laid out again.
+    Relayout,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
 pub enum UpdateType {
     Aur,
     Pacman,
     Devel,
+    HeldBack,
+    Flatpak,
 }
 
 impl Application for CosmicAppletArch {
     // Use the default Cosmic executor.
     type Executor = cosmic::executor::Default;
     // Config data type for init function.
-    // TODO: Add configuration.
-    type Flags = ();
+    type Flags = Config;
     type Message = Message;
     const APP_ID: &'static str = "com.nick42d.CosmicAppletArch";
 
@@ -80,11 +127,19 @@ impl Application for CosmicAppletArch {
     // Core is passed by libcosmic, and caller can pass some state in Flags.
     // On load we can immediately run an async task by returning a Task as the
     // second component of the tuple.
-    fn init(core: Core, _flags: Self::Flags) -> (Self, Task<Self::Message>) {
-        let app = CosmicAppletArch {
+    fn init(core: Core, flags: Self::Flags) -> (Self, Task<Self::Message>) {
+        let mut app = CosmicAppletArch {
             core,
+            config: flags,
             ..Default::default()
         };
+        // Lists keep their state between popup opens, so are only set from the
+        // config once.
+        app.pacman_list_state = app.default_list_state(UpdateType::Pacman);
+        app.aur_list_state = app.default_list_state(UpdateType::Aur);
+        app.devel_list_state = app.default_list_state(UpdateType::Devel);
+        app.held_back_list_state = app.default_list_state(UpdateType::HeldBack);
+        app.flatpak_list_state = app.default_list_state(UpdateType::Flatpak);
         (app, Task::none())
     }
     fn on_close_requested(&self, id: Id) -> Option<Message> {
@@ -109,13 +164,22 @@ impl Application for CosmicAppletArch {
                 checked_online_time,
             } => self.handle_updates(updates, checked_online_time),
             Message::ForceGetUpdates => self.handle_force_get_updates(),
+            Message::SyncDatabase => self.handle_sync_database(),
+            Message::TogglePaused => self.handle_toggle_paused(),
             Message::ToggleCollapsible(update_type) => self.handle_toggle_collapsible(update_type),
             Message::CheckUpdatesErrorsMsg(e) => self.handle_update_error(e),
+            Message::CheckUpdatesProgressMsg(progress) => self.handle_progress(progress),
+            Message::OnlineChecksPausedMsg(paused) => self.handle_online_checks_paused(paused),
+            Message::NextOnlineCheckMsg(time) => self.handle_next_online_check(time),
+            Message::Relayout => Task::none(),
         }
     }
     // Long running stream of messages to the app.
     fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
-        subscription::subscription(self)
+        cosmic::iced::Subscription::batch([
+            subscription::subscription(self),
+            dbus::subscription(self),
+        ])
     }
 }
 
@@ -124,9 +188,6 @@ impl CosmicAppletArch {
         if let Some(p) = self.popup.take() {
             destroy_popup(p)
         } else {
-            self.pacman_list_state = Collapsed::Collapsed;
-            self.aur_list_state = Collapsed::Collapsed;
-            self.devel_list_state = Collapsed::Collapsed;
             let new_id = Id::unique();
             self.popup.replace(new_id);
             let mut popup_settings = self.core.applet.get_popup_settings(
@@ -139,18 +200,28 @@ impl CosmicAppletArch {
                 None,
             );
             popup_settings.positioner.size_limits = Limits::NONE
-                .max_width(444.0)
-                .min_width(300.0)
-                .min_height(200.0)
-                .max_height(1080.0);
+                .max_width(self.config.popup_max_width as f32)
+                .min_width(self.config.popup_min_width as f32)
+                .min_height(MIN_POPUP_HEIGHT as f32)
+                .max_height(self.config.popup_max_height as f32);
             get_popup(popup_settings)
         }
     }
+    /// The state a list is in when the popup is first opened.
+    fn default_list_state(&self, update_type: UpdateType) -> Collapsed {
+        if self.config.default_expanded.contains(&update_type) {
+            Collapsed::Expanded
+        } else {
+            Collapsed::Collapsed
+        }
+    }
     fn handle_toggle_collapsible(&mut self, update_type: UpdateType) -> Task<Message> {
         match update_type {
             UpdateType::Aur => self.aur_list_state = self.aur_list_state.toggle(),
             UpdateType::Pacman => self.pacman_list_state = self.pacman_list_state.toggle(),
             UpdateType::Devel => self.devel_list_state = self.devel_list_state.toggle(),
+            UpdateType::HeldBack => self.held_back_list_state = self.held_back_list_state.toggle(),
+            UpdateType::Flatpak => self.flatpak_list_state = self.flatpak_list_state.toggle(),
         }
         Task::none()
     }
@@ -161,30 +232,96 @@ impl CosmicAppletArch {
         Task::none()
     }
     fn handle_force_get_updates(&mut self) -> Task<Message> {
+        self.refreshing = true;
         self.refresh_pressed_notifier.notify_one();
         Task::none()
     }
+    /// Pauses or resumes background checks. Resuming runs an online check
+    /// straight away, as scheduled checks may have been missed.
+    fn handle_toggle_paused(&mut self) -> Task<Message> {
+        let paused = !self.is_user_paused();
+        self.user_paused.send_replace(paused);
+        if !paused {
+            self.handle_force_get_updates()
+        } else {
+            Task::none()
+        }
+    }
+    fn is_user_paused(&self) -> bool {
+        *self.user_paused.borrow()
+    }
+    /// Runs the configured sync command, then an online check once it succeeds.
+    fn handle_sync_database(&mut self) -> Task<Message> {
+        let Some((program, args)) = self
+            .config
+            .sync_database_command
+            .as_ref()
+            .and_then(|command| command.split_first())
+        else {
+            return Task::none();
+        };
+        let mut command = tokio::process::Command::new(program);
+        command.args(args);
+        Task::perform(async move { command.status().await }, |status| {
+            cosmic::app::Message::App(match status {
+                Ok(status) if status.success() => Message::ForceGetUpdates,
+                Ok(status) => Message::CheckUpdatesErrorsMsg(format!(
+                    "Sync database command failed with {status}"
+                )),
+                Err(e) => Message::CheckUpdatesErrorsMsg(format!(
+                    "Error {e} running sync database command"
+                )),
+            })
+        })
+    }
     fn handle_update_error(&mut self, error: String) -> Task<Message> {
         self.error = Some(error);
+        self.progress = None;
+        self.refreshing = false;
+        Task::none()
+    }
+    fn handle_progress(&mut self, progress: Progress) -> Task<Message> {
+        self.progress = Some(progress);
+        Task::none()
+    }
+    fn handle_online_checks_paused(&mut self, paused: bool) -> Task<Message> {
+        self.online_checks_paused = paused;
+        Task::none()
+    }
+    fn handle_next_online_check(&mut self, time: DateTime<Local>) -> Task<Message> {
+        self.next_online_check = Some(time);
         Task::none()
     }
     fn handle_updates(&mut self, updates: Updates, time: Option<DateTime<Local>>) -> Task<Message> {
         // When first receiving updates, autosize will not trigger until the second
-        // message is received. So, we intentionally bounce this message if it's
-        // the first time updates have been received.
+        // message is received. libcosmic's autosize widget has no way to request a
+        // relayout directly, so we send a no-op message if it's the first time
+        // updates have been received. This avoids processing the updates twice.
         let task: Task<Message> = if self.updates.is_none() {
-            Task::done(cosmic::app::Message::App(Message::CheckUpdatesMsg {
-                updates: updates.clone(),
-                checked_online_time: time,
-            }))
+            Task::done(cosmic::app::Message::App(Message::Relayout))
         } else {
             Task::none()
         };
-        self.updates = Some(updates);
         if let Some(time) = time {
             self.last_checked = Some(time);
+            // Offline results may still arrive while the refresh is running.
+            self.refreshing = false;
         }
+        let counts = dbus::UpdateCounts {
+            pacman: updates.pacman.len() as u32,
+            aur: updates.aur.len() as u32,
+            devel: updates.devel.len() as u32,
+            last_checked: self.last_checked,
+        };
+        // Only notify D-Bus clients if something changed.
+        self.dbus_counts.send_if_modified(|current| {
+            let modified = *current != counts;
+            *current = counts;
+            modified
+        });
+        self.updates = Some(updates);
         self.error = None;
+        self.progress = None;
         task
     }
 }
@@ -0,0 +1,102 @@
+//! D-Bus service publishing the applet's update counts, so that other tools
+//! (e.g other status bars) can reuse them instead of running their own checks.
+//!
+//! Service `com.nick42d.CosmicAppletArch`, object
+//! `/com/nick42d/CosmicAppletArch`, interface `com.nick42d.CosmicAppletArch1`:
+//!  - Properties `PacmanCount`, `AurCount` and `DevelCount` (u32).
+//!  - Property `LastChecked` (RFC 3339 string, empty if not yet checked).
+//!  - Method `Refresh()` to force an online check.
+use super::{CosmicAppletArch, Message};
+use chrono::{DateTime, Local};
+use cosmic::iced::futures::channel::mpsc;
+use cosmic::Application;
+use std::sync::Arc;
+use tokio::sync::{watch, Notify};
+
+const OBJECT_PATH: &str = "/com/nick42d/CosmicAppletArch";
+
+/// Latest update counts, as published on D-Bus.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UpdateCounts {
+    pub pacman: u32,
+    pub aur: u32,
+    pub devel: u32,
+    pub last_checked: Option<DateTime<Local>>,
+}
+
+struct ArchUpdatesInterface {
+    counts: watch::Receiver<UpdateCounts>,
+    refresh_pressed_notifier: Arc<Notify>,
+}
+
+#[zbus::interface(name = "com.nick42d.CosmicAppletArch1")]
+impl ArchUpdatesInterface {
+    #[zbus(property)]
+    fn pacman_count(&self) -> u32 {
+        self.counts.borrow().pacman
+    }
+    #[zbus(property)]
+    fn aur_count(&self) -> u32 {
+        self.counts.borrow().aur
+    }
+    #[zbus(property)]
+    fn devel_count(&self) -> u32 {
+        self.counts.borrow().devel
+    }
+    #[zbus(property)]
+    fn last_checked(&self) -> String {
+        self.counts
+            .borrow()
+            .last_checked
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default()
+    }
+    fn refresh(&self) {
+        self.refresh_pressed_notifier.notify_one();
+    }
+}
+
+/// Long running D-Bus service. Sends no messages to the app, it reads the
+/// update counts from `app.dbus_counts`.
+pub fn subscription(app: &CosmicAppletArch) -> cosmic::iced::Subscription<Message> {
+    let counts = app.dbus_counts.subscribe();
+    let refresh_pressed_notifier = app.refresh_pressed_notifier.clone();
+    let worker = |_: mpsc::Sender<Message>| async move {
+        if let Err(e) = serve(counts, refresh_pressed_notifier).await {
+            eprintln!("Error {e} running D-Bus service");
+        }
+        // Don't return, as the subscription would be restarted.
+        std::future::pending::<()>().await;
+    };
+    let stream = cosmic::iced_futures::stream::channel(1, worker);
+    cosmic::iced::Subscription::run_with_id("arch-updates-dbus", stream)
+}
+
+async fn serve(
+    mut counts: watch::Receiver<UpdateCounts>,
+    refresh_pressed_notifier: Arc<Notify>,
+) -> zbus::Result<()> {
+    let interface = ArchUpdatesInterface {
+        counts: counts.clone(),
+        refresh_pressed_notifier,
+    };
+    let connection = zbus::connection::Builder::session()?
+        .name(CosmicAppletArch::APP_ID)?
+        .serve_at(OBJECT_PATH, interface)?
+        .build()
+        .await?;
+    let interface_ref = connection
+        .object_server()
+        .interface::<_, ArchUpdatesInterface>(OBJECT_PATH)
+        .await?;
+    // Notify D-Bus clients when the counts change.
+    while counts.changed().await.is_ok() {
+        let interface = interface_ref.get().await;
+        let ctxt = interface_ref.signal_context();
+        interface.pacman_count_changed(ctxt).await?;
+        interface.aur_count_changed(ctxt).await?;
+        interface.devel_count_changed(ctxt).await?;
+        interface.last_checked_changed(ctxt).await?;
+    }
+    Ok(())
+}
@@ -1,15 +1,27 @@
-use super::{CosmicAppletArch, Message, CYCLES, INTERVAL, SUBSCRIPTION_BUF_SIZE};
-use crate::app::TIMEOUT;
-use arch_updates_rs::{DevelUpdate, Update};
-use chrono::{DateTime, Local};
+use super::{
+    CosmicAppletArch, Message, CYCLES, INTERVAL, RETRY_BACKOFF_MAX, RETRY_BACKOFF_MIN,
+    SUBSCRIPTION_BUF_SIZE, USER_AGENT,
+};
+use crate::{app::TIMEOUT, fl};
+use arch_updates_rs::{
+    AllUpdates, AurUpdate, DevelUpdate, FlatpakUpdate, Package, Progress, Update, UpdateChecker,
+    UpdateCheckerOptions,
+};
+use chrono::{DateTime, Local, TimeDelta};
 use cosmic::iced::futures::{channel::mpsc, SinkExt};
-use futures::TryFutureExt;
-use std::future::Future;
-use tokio::join;
+use futures::{StreamExt, TryFutureExt};
+use std::{future::Future, time::Duration};
+use tokio::{join, time::Instant};
 
 // Long running stream of messages to the app.
 pub fn subscription(app: &CosmicAppletArch) -> cosmic::iced::Subscription<Message> {
     let notifier = app.refresh_pressed_notifier.clone();
+    let pause_on_battery = app.config.pause_on_battery;
+    let proxy = app.config.proxy.clone();
+    let http_timeout = Duration::from_secs(app.config.http_timeout_secs);
+    let ignore_pkgrel_only = app.config.ignore_pkgrel_only;
+    let check_flatpak = app.config.check_flatpak;
+    let user_paused = app.user_paused.subscribe();
     async fn send_error(tx: &mut mpsc::Sender<Message>, e: impl std::fmt::Display) {
         tx.send(Message::CheckUpdatesErrorsMsg(format!("{e}")))
             .await
@@ -33,36 +45,85 @@ pub fn subscription(app: &CosmicAppletArch) -> cosmic::iced::Subscription<Messag
             eprintln!("Error {e} sending Arch update status - maybe the applet has been dropped.")
         });
     }
+    async fn send_next_online_check(tx: &mut mpsc::Sender<Message>, time: DateTime<Local>) {
+        tx.send(Message::NextOnlineCheckMsg(time))
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!(
+                    "Error {e} sending Arch update status - maybe the applet has been dropped."
+                )
+            });
+    }
+    async fn send_paused(tx: &mut mpsc::Sender<Message>, paused: bool) {
+        tx.send(Message::OnlineChecksPausedMsg(paused))
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!(
+                    "Error {e} sending Arch update status - maybe the applet has been dropped."
+                )
+            });
+    }
     // TODO: Determine if INTERVAL is sufficient to prevent too many timeouts.
     let worker = |mut tx: mpsc::Sender<Message>| async move {
         let mut counter = 0;
         // If we have no cache, that means we haven't run a succesful online check.
         // Offline checks will be skipped until we can run one.
         let mut cache = None;
+        let mut retry = OnlineRetry::default();
+        let mut paused = false;
+        // Reused between checks so that HTTP connections can be reused.
+        let checker = new_update_checker(proxy, http_timeout);
         let mut interval = tokio::time::interval(INTERVAL);
         interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
         loop {
             let notified = notifier.notified();
             tokio::select! {
                 _ = interval.tick() => {
-                    let check_type = match counter {
-                        0 => CheckType::Online,
+                    // Resuming sends a refresh, so there's no need to catch up here.
+                    if *user_paused.borrow() {
+                        continue;
+                    }
+                    let paused_tmp = pause_on_battery && on_battery().await;
+                    if paused_tmp != paused {
+                        paused = paused_tmp;
+                        send_paused(&mut tx, paused).await;
+                    }
+                    let mut check_type = match (counter, &cache) {
+                        (0, _) => CheckType::Online,
+                        (_, None) if retry.is_due() => CheckType::Online,
                         _ => CheckType::Offline,
                     };
-                    counter += 1;
-                    if counter > CYCLES {
-                        counter = 0
+                    // While paused, the counter is held so that the online check runs as
+                    // soon as checks resume.
+                    if paused && matches!(check_type, CheckType::Online) {
+                        check_type = CheckType::Offline;
+                    } else {
+                        counter += 1;
+                        if counter >= CYCLES {
+                            counter = 0
+                        }
                     }
-                    let updates = match (&check_type, &cache) {
+                    // AUR packages installed since the last online check aren't in the cache,
+                    // so can only be checked online.
+                    if let (CheckType::Offline, Some(cache), false) = (check_type, &cache, paused) {
+                        if has_uncached_packages(cache).await {
+                            check_type = CheckType::Online;
+                        }
+                    }
+                    let mut updates = match (&check_type, &cache) {
                         (CheckType::Online, _) => {
-                            match flat_erased_timeout(TIMEOUT, get_updates_online()).await {
+                            match flat_erased_timeout(TIMEOUT, get_updates_online_with_progress(&checker, check_flatpak, tx.clone())).await {
                                 Err(e) => {
                                     cache = None;
-                                    send_error(&mut tx, e).await;
+                                    retry.failed();
+                                    send_next_online_check(&mut tx, next_online_check(counter, &retry, false)).await;
+                                    send_error(&mut tx, explain_online_error(e).await).await;
                                     continue;
                                 },
                                 Ok((updates, cache_tmp)) => {
                                     cache = Some(cache_tmp);
+                                    retry.succeeded();
+                                    send_next_online_check(&mut tx, next_online_check(counter, &retry, true)).await;
                                     updates
                                 }
                             }
@@ -82,21 +143,30 @@ pub fn subscription(app: &CosmicAppletArch) -> cosmic::iced::Subscription<Messag
                         CheckType::Online => Some(Local::now()),
                         CheckType::Offline => None,
                     };
+                    if ignore_pkgrel_only {
+                        updates.remove_pkgrel_only();
+                    }
                     send_update(&mut tx, updates, checked_online_time).await;
                 }
                 _ = notified => {
                     counter = 1;
-                    let updates = flat_erased_timeout(TIMEOUT, get_updates_online()).await;
+                    let updates = flat_erased_timeout(TIMEOUT, get_updates_online_with_progress(&checker, check_flatpak, tx.clone())).await;
                     match updates {
-                        Ok((updates, cache_tmp)) => {
+                        Ok((mut updates, cache_tmp)) => {
                             cache = Some(cache_tmp);
+                            retry.succeeded();
+                            if ignore_pkgrel_only {
+                                updates.remove_pkgrel_only();
+                            }
                             send_update(&mut tx, updates, Some(Local::now())).await;
                         },
                         Err(e) => {
                             cache = None;
-                            send_error(&mut tx, e).await;
+                            retry.failed();
+                            send_error(&mut tx, explain_online_error(e).await).await;
                         }
                     }
+                    send_next_online_check(&mut tx, next_online_check(counter, &retry, cache.is_some())).await;
                 }
             }
         }
@@ -105,23 +175,127 @@ pub fn subscription(app: &CosmicAppletArch) -> cosmic::iced::Subscription<Messag
     cosmic::iced::Subscription::run_with_id("arch-updates-sub", stream)
 }
 
+/// Returns true if the system has a mains power supply, and it's offline.
+/// Systems without a mains power supply (e.g desktops without a UPS reporting
+/// to the system) are never considered to be on battery.
+async fn on_battery() -> bool {
+    let Ok(mut entries) = tokio::fs::read_dir("/sys/class/power_supply").await else {
+        return false;
+    };
+    let mut has_mains = false;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let Ok(supply_type) = tokio::fs::read_to_string(path.join("type")).await else {
+            continue;
+        };
+        if supply_type.trim() != "Mains" {
+            continue;
+        }
+        has_mains = true;
+        if tokio::fs::read_to_string(path.join("online"))
+            .await
+            .is_ok_and(|online| online.trim() == "1")
+        {
+            return false;
+        }
+    }
+    has_mains
+}
+
+/// When the next scheduled online check is due. Online checks run on the
+/// `INTERVAL` tick where `counter` is 0, or sooner if a failed check is being
+/// retried while there is no cache.
+fn next_online_check(counter: usize, retry: &OnlineRetry, has_cache: bool) -> DateTime<Local> {
+    let ticks = (CYCLES - counter) % CYCLES + 1;
+    let mut remaining = INTERVAL * ticks as u32;
+    if let (false, Some(retry_in)) = (has_cache, retry.time_until_next_attempt()) {
+        remaining = remaining.min(retry_in);
+    }
+    Local::now() + TimeDelta::from_std(remaining).unwrap_or(TimeDelta::zero())
+}
+
 #[derive(Clone, Copy, Debug)]
 enum CheckType {
     Online,
     Offline,
 }
 
+/// Tracks when to retry a failed online check, for when there is no cache from
+/// a previous successful online check (e.g. the network was down at startup).
+struct OnlineRetry {
+    backoff: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl Default for OnlineRetry {
+    fn default() -> Self {
+        Self {
+            backoff: RETRY_BACKOFF_MIN,
+            next_attempt: None,
+        }
+    }
+}
+
+impl OnlineRetry {
+    fn is_due(&self) -> bool {
+        self.next_attempt
+            .is_some_and(|next_attempt| Instant::now() >= next_attempt)
+    }
+    fn time_until_next_attempt(&self) -> Option<Duration> {
+        self.next_attempt
+            .map(|next_attempt| next_attempt.saturating_duration_since(Instant::now()))
+    }
+    fn failed(&mut self) {
+        self.next_attempt = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(RETRY_BACKOFF_MAX);
+    }
+    fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+}
+
 #[derive(Default, Clone)]
 struct CacheState {
-    aur_cache: Vec<Update>,
+    aur_cache: Vec<AurUpdate>,
     devel_cache: Vec<DevelUpdate>,
+    orphaned_cache: Vec<Package>,
+    /// Flatpak updates can only be checked online, so offline checks reuse the
+    /// result of the last online check.
+    flatpak_cache: Vec<FlatpakUpdate>,
+    flatpak_warning: Option<String>,
+    /// Devel packages that couldn't be checked, kept so offline checks still
+    /// show them.
+    devel_warnings: Vec<String>,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct Updates {
     pub pacman: Vec<Update>,
-    pub aur: Vec<Update>,
+    pub aur: Vec<AurUpdate>,
     pub devel: Vec<DevelUpdate>,
+    /// Pacman updates for ignored packages, that are being held back.
+    pub held_back: Vec<Update>,
+    /// Installed AUR packages that are no longer on the AUR.
+    pub orphaned: Vec<Package>,
+    /// Empty unless flatpak checks are enabled in the config.
+    pub flatpak: Vec<FlatpakUpdate>,
+    /// When the pacman sync database used for the checks was last synced, if
+    /// known.
+    pub pacman_db_synced: Option<DateTime<Local>>,
+    /// A reboot is recommended, e.g the running kernel or systemd has been
+    /// upgraded.
+    pub reboot_recommended: bool,
+    /// Non-fatal problems encountered during the check, to show to the user.
+    pub warnings: Vec<String>,
+}
+
+impl Updates {
+    /// Remove pacman and AUR updates where only the pkgrel has changed.
+    fn remove_pkgrel_only(&mut self) {
+        self.pacman.retain(|update| !update.is_pkgrel_only());
+        self.held_back.retain(|update| !update.is_pkgrel_only());
+        self.aur.retain(|update| !update.is_pkgrel_only());
+    }
 }
 
 /// Helper function - adds a timeout to a future that returns a result.
@@ -141,53 +315,243 @@ where
 }
 
 async fn get_updates_offline(cache: &CacheState) -> arch_updates_rs::Result<Updates> {
-    #[cfg(feature = "mock-api")]
-    return mock::get_mock_updates().await;
+    if let Some(path) = mock::mock_updates_path() {
+        return mock::get_mock_updates(path).await;
+    }
 
     let CacheState {
         aur_cache,
         devel_cache,
+        orphaned_cache,
+        flatpak_cache,
+        flatpak_warning,
+        devel_warnings,
     } = cache;
     let (pacman, aur, devel) = join!(
         arch_updates_rs::check_pacman_updates_offline(),
         arch_updates_rs::check_aur_updates_offline(aur_cache),
         arch_updates_rs::check_devel_updates_offline(devel_cache),
     );
+    let (held_back, held_back_warning) = get_held_back_updates().await;
+    let (pacman_db_synced, db_synced_warning) = get_pacman_db_synced().await;
+    let (reboot_recommended, reboot_warning) = get_reboot_recommended().await;
     Ok(Updates {
         pacman: pacman?,
         aur: aur?,
         devel: devel?,
+        held_back,
+        orphaned: orphaned_cache.clone(),
+        flatpak: flatpak_cache.clone(),
+        pacman_db_synced,
+        reboot_recommended,
+        warnings: [
+            held_back_warning,
+            flatpak_warning.clone(),
+            db_synced_warning,
+            reboot_warning,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(devel_warnings.iter().cloned())
+        .collect(),
     })
 }
 
-async fn get_updates_online() -> arch_updates_rs::Result<(Updates, CacheState)> {
-    let (pacman, aur, devel) = join!(
-        arch_updates_rs::check_pacman_updates_online(),
-        arch_updates_rs::check_aur_updates_online(),
-        arch_updates_rs::check_devel_updates_online(),
+/// Returns true if any AUR packages have been installed since the cache was
+/// created. Packages that aren't on the AUR are never in the cache, so are
+/// ignored.
+async fn has_uncached_packages(cache: &CacheState) -> bool {
+    if mock::mock_updates_path().is_some() {
+        return false;
+    }
+
+    match arch_updates_rs::get_uncached_aur_packages(&cache.aur_cache).await {
+        Ok(uncached) => uncached.iter().any(|package| {
+            !cache
+                .orphaned_cache
+                .iter()
+                .any(|orphaned| orphaned.pkgname == package.pkgname)
+        }),
+        Err(e) => {
+            eprintln!("Error {e} checking for newly installed AUR packages");
+            false
+        }
+    }
+}
+
+fn new_update_checker(proxy: Option<String>, http_timeout: Duration) -> UpdateChecker {
+    let mut options = UpdateCheckerOptions::default();
+    options.user_agent = USER_AGENT.to_string();
+    options.proxy = proxy;
+    options.http_timeout = Some(http_timeout);
+    UpdateChecker::with_options(options).unwrap_or_else(|e| {
+        eprintln!("Error {e} setting up update checker, using default options");
+        UpdateChecker::new()
+    })
+}
+
+/// Run an online check, forwarding its progress to the app while it runs.
+async fn get_updates_online_with_progress(
+    checker: &UpdateChecker,
+    check_flatpak: bool,
+    tx: mpsc::Sender<Message>,
+) -> arch_updates_rs::Result<(Updates, CacheState)> {
+    let (progress_tx, progress_rx) = mpsc::channel(SUBSCRIPTION_BUF_SIZE);
+    // Completes once the check drops its progress sender.
+    let forward_progress = progress_rx
+        .map(|progress| Ok::<_, mpsc::SendError>(Message::CheckUpdatesProgressMsg(progress)))
+        .forward(tx);
+    let (updates, _) = join!(
+        get_updates_online(checker, check_flatpak, Some(progress_tx)),
+        forward_progress
+    );
+    updates
+}
+
+async fn get_updates_online(
+    checker: &UpdateChecker,
+    check_flatpak: bool,
+    progress: Option<mpsc::Sender<Progress>>,
+) -> arch_updates_rs::Result<(Updates, CacheState)> {
+    if let Some(path) = mock::mock_updates_path() {
+        return Ok((mock::get_mock_updates(path).await?, CacheState::default()));
+    }
+    // The combined check shares the installed foreign packages between the AUR
+    // and devel checks, rather than running `pacman -Qm` for each.
+    let ((updates, cache), flatpak) = join!(
+        checker.check_all_updates_online_with_progress(progress),
+        get_flatpak_updates(check_flatpak),
     );
-    let (aur, aur_cache) = aur?;
-    let (devel, devel_cache) = devel?;
+    let AllUpdates {
+        pacman,
+        aur,
+        devel,
+        warnings,
+    } = updates;
+    // The AUR check already finds packages that are no longer on the AUR.
+    let orphaned = cache.orphaned.unwrap_or_default();
+    let devel_warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+    let (flatpak, flatpak_warning) = flatpak;
+    let (held_back, held_back_warning) = get_held_back_updates().await;
+    let (pacman_db_synced, db_synced_warning) = get_pacman_db_synced().await;
+    let (reboot_recommended, reboot_warning) = get_reboot_recommended().await;
     Ok((
         Updates {
             pacman: pacman?,
-            aur,
-            devel,
+            aur: aur?,
+            devel: devel?,
+            held_back,
+            orphaned: orphaned.clone(),
+            flatpak: flatpak.clone(),
+            pacman_db_synced,
+            reboot_recommended,
+            warnings: [
+                held_back_warning,
+                flatpak_warning.clone(),
+                db_synced_warning,
+                reboot_warning,
+            ]
+            .into_iter()
+            .flatten()
+            .chain(devel_warnings.iter().cloned())
+            .collect(),
         },
         CacheState {
-            aur_cache,
-            devel_cache,
+            aur_cache: cache.aur.unwrap_or_default(),
+            devel_cache: cache.devel.unwrap_or_default(),
+            orphaned_cache: orphaned,
+            flatpak_cache: flatpak,
+            flatpak_warning,
+            devel_warnings,
         },
     ))
 }
 
-#[cfg(feature = "mock-api")]
-/// This module provides a way to feed mock data to the app when compiled with
-/// the mock-api feature using the mock_updates.ron file.
+/// Online check errors from a missing program or unwritable directory are
+/// unclear, e.g "No such file or directory", so explain what's missing.
+async fn explain_online_error(error: String) -> String {
+    let report = arch_updates_rs::preflight().await;
+    if !report.checkupdates_installed {
+        fl!("preflight-no-checkupdates")
+    } else if !report.lock_dir_writable {
+        fl!("preflight-lock-dir")
+    } else if !report.git_installed {
+        // git is only needed if there are devel packages, so it may not be the
+        // cause.
+        format!("{error} - {}", fl!("preflight-no-git"))
+    } else {
+        error
+    }
+}
+
+/// The sync time is informational only, so failing to get it shouldn't fail the
+/// whole check.
+async fn get_pacman_db_synced() -> (Option<DateTime<Local>>, Option<String>) {
+    let (synced, warning) = or_warning(
+        arch_updates_rs::get_pacman_db_last_synced().await,
+        |error| fl!("warning-db-synced", error = error),
+    );
+    (synced.map(Into::into), warning)
+}
+
+/// The reboot recommendation is informational only, so failing to get it
+/// shouldn't fail the whole check.
+async fn get_reboot_recommended() -> (bool, Option<String>) {
+    let (recommendation, warning) =
+        or_warning(arch_updates_rs::check_reboot_recommended().await, |error| {
+            fl!("warning-reboot", error = error)
+        });
+    (recommendation.is_recommended(), warning)
+}
+
+/// Held back updates are informational only, so failing to get them shouldn't
+/// fail the whole check.
+async fn get_held_back_updates() -> (Vec<Update>, Option<String>) {
+    or_warning(
+        arch_updates_rs::check_pacman_ignored_updates().await,
+        |error| fl!("warning-held-back", error = error),
+    )
+}
+
+/// Flatpak updates are an optional extra source, so failing to get them
+/// shouldn't fail the whole check.
+async fn get_flatpak_updates(check_flatpak: bool) -> (Vec<FlatpakUpdate>, Option<String>) {
+    if !check_flatpak {
+        return (Vec::new(), None);
+    }
+    or_warning(
+        arch_updates_rs::check_flatpak_updates_online().await,
+        |error| fl!("warning-flatpak", error = error),
+    )
+}
+
+/// Helper function - on error, returns the default value along with a warning
+/// to show to the user, built from the error message.
+fn or_warning<T: Default>(
+    result: arch_updates_rs::Result<T>,
+    warning: impl FnOnce(String) -> String,
+) -> (T, Option<String>) {
+    match result {
+        Ok(t) => (t, None),
+        Err(e) => {
+            let warning = warning(e.to_string());
+            eprintln!("{warning}");
+            (T::default(), Some(warning))
+        }
+    }
+}
+
+/// This module provides a way to feed mock data to the app, either when
+/// compiled with the mock-api feature using the mock_updates.ron file, or at
+/// runtime using the file in the `COSMIC_APPLET_ARCH_MOCK` environment
+/// variable.
 mod mock {
     use super::Updates;
-    use arch_updates_rs::{DevelUpdate, Update};
+    use arch_updates_rs::{AurUpdate, DevelUpdate, Update};
     use serde::Deserialize;
+    use std::path::PathBuf;
+
+    const MOCK_ENV_VAR: &str = "COSMIC_APPLET_ARCH_MOCK";
 
     #[derive(Clone, Debug, Default, Deserialize)]
     pub struct MockUpdates {
@@ -217,6 +581,12 @@ mod mock {
                 pacman: pacman.into_iter().map(Into::into).collect(),
                 aur: aur.into_iter().map(Into::into).collect(),
                 devel: devel.into_iter().map(Into::into).collect(),
+                held_back: Vec::new(),
+                orphaned: Vec::new(),
+                flatpak: Vec::new(),
+                pacman_db_synced: None,
+                reboot_recommended: false,
+                warnings: Vec::new(),
             }
         }
     }
@@ -232,7 +602,31 @@ mod mock {
                 pkgname,
                 pkgver_cur,
                 pkgrel_cur,
+                ref_id_full: ref_id_new.clone(),
                 ref_id_new,
+                remote_commit_date: None,
+                checked_at: chrono::Utc::now(),
+                pkgbase: None,
+            }
+        }
+    }
+    impl From<MockUpdate> for AurUpdate {
+        fn from(value: MockUpdate) -> AurUpdate {
+            let MockUpdate {
+                pkgname,
+                pkgver_cur,
+                pkgrel_cur,
+                pkgver_new,
+                pkgrel_new,
+            } = value;
+            AurUpdate {
+                pkgname,
+                pkgver_cur,
+                pkgrel_cur,
+                pkgver_new,
+                pkgrel_new,
+                flagged_out_of_date: None,
+                pkgbase: None,
             }
         }
     }
@@ -251,13 +645,91 @@ mod mock {
                 pkgrel_cur,
                 pkgver_new,
                 pkgrel_new,
+                hold: false,
             }
         }
     }
 
-    pub async fn get_mock_updates() -> arch_updates_rs::Result<Updates> {
-        let file = tokio::fs::read_to_string("mock_updates.ron").await.unwrap();
-        let updates: MockUpdates = ron::from_str(&file).unwrap();
+    /// Path to the mock updates file, if mock data should be used.
+    pub fn mock_updates_path() -> Option<PathBuf> {
+        if cfg!(feature = "mock-api") {
+            return Some(PathBuf::from("mock_updates.ron"));
+        }
+        std::env::var_os(MOCK_ENV_VAR).map(PathBuf::from)
+    }
+
+    pub async fn get_mock_updates(path: PathBuf) -> arch_updates_rs::Result<Updates> {
+        let file = tokio::fs::read_to_string(path).await?;
+        let updates: MockUpdates = ron::from_str(&file).map_err(std::io::Error::other)?;
         Ok(updates.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{next_online_check, OnlineRetry, Updates};
+    use crate::app::{INTERVAL, RETRY_BACKOFF_MIN};
+    use arch_updates_rs::{AurUpdate, Update};
+    use chrono::{Local, TimeDelta};
+
+    fn update(pkgname: &str, pkgver_new: &str) -> Update {
+        Update {
+            pkgname: pkgname.to_string(),
+            pkgver_cur: "1.0".to_string(),
+            pkgrel_cur: "1".to_string(),
+            pkgver_new: pkgver_new.to_string(),
+            pkgrel_new: "2".to_string(),
+            hold: false,
+        }
+    }
+    fn aur_update(pkgname: &str, pkgver_new: &str) -> AurUpdate {
+        AurUpdate {
+            pkgname: pkgname.to_string(),
+            pkgver_cur: "1.0".to_string(),
+            pkgrel_cur: "1".to_string(),
+            pkgver_new: pkgver_new.to_string(),
+            pkgrel_new: "2".to_string(),
+            flagged_out_of_date: None,
+            pkgbase: None,
+        }
+    }
+    #[test]
+    fn test_remove_pkgrel_only() {
+        let mut updates = Updates {
+            pacman: vec![update("pacman-rebuild", "1.0"), update("pacman-new", "1.1")],
+            held_back: vec![update("held-rebuild", "1.0"), update("held-new", "1.1")],
+            aur: vec![
+                aur_update("aur-rebuild", "1.0"),
+                aur_update("aur-new", "1.1"),
+            ],
+            ..Default::default()
+        };
+        updates.remove_pkgrel_only();
+        let pkgnames = |updates: &[Update]| {
+            updates
+                .iter()
+                .map(|update| update.pkgname.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pkgnames(&updates.pacman), vec!["pacman-new"]);
+        assert_eq!(pkgnames(&updates.held_back), vec!["held-new"]);
+        assert_eq!(updates.aur, vec![aur_update("aur-new", "1.1")]);
+    }
+    #[test]
+    fn test_next_online_check() {
+        let interval = TimeDelta::from_std(INTERVAL).unwrap();
+        let backoff = TimeDelta::from_std(RETRY_BACKOFF_MIN).unwrap();
+        // Counter 0 means the next tick is an online check.
+        let next = next_online_check(0, &OnlineRetry::default(), true);
+        assert!(next - Local::now() <= interval);
+        let next = next_online_check(1, &OnlineRetry::default(), true);
+        assert!(next - Local::now() > backoff);
+        // Without a cache, a failed check is retried sooner.
+        let mut retry = OnlineRetry::default();
+        retry.failed();
+        let next = next_online_check(1, &retry, false);
+        assert!(next - Local::now() <= backoff);
+        let next = next_online_check(1, &retry, true);
+        assert!(next - Local::now() > backoff);
+    }
+}
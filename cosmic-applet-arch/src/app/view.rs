@@ -1,6 +1,10 @@
 use super::{CosmicAppletArch, Message};
-use crate::fl;
-use arch_updates_rs::{DevelUpdate, Update};
+use crate::{
+    config::{IconConfig, PanelCountStyle},
+    fl,
+};
+use arch_updates_rs::{AurUpdate, DevelUpdate, FlatpakUpdate, Package, Update};
+use chrono::{DateTime, Local, TimeDelta};
 use cosmic::{
     app::Core,
     iced::{
@@ -14,7 +18,8 @@ use cosmic::{
 use std::{borrow::Cow, fmt::Display};
 use std::{rc::Rc, sync::LazyLock};
 
-const MAX_LINES: usize = 20;
+/// Maximum height of an expanded update list, beyond which it scrolls.
+const MAX_LIST_HEIGHT: f32 = 400.0;
 
 // This is the same mechanism the official cosmic applets use.
 static AUTOSIZE_MAIN_ID: LazyLock<Id> = LazyLock::new(|| Id::new("autosize-main"));
@@ -24,16 +29,25 @@ enum AppIcon {
     Error,
     UpdatesAvailable,
     UpToDate,
+    Stale,
+    Paused,
 }
 
 impl AppIcon {
-    fn to_str(&self) -> &'static str {
-        match self {
-            AppIcon::UpdatesAvailable => "software-update-available-symbolic",
-            AppIcon::UpToDate => "emblem-default-symbolic",
-            AppIcon::Loading => "emblem-synchronizing-symbolic",
-            AppIcon::Error => "dialog-error-symbolic",
-        }
+    /// Icon name, using the override from the config if set.
+    fn to_str<'a>(&self, config: &'a IconConfig) -> &'a str {
+        let (icon_override, default) = match self {
+            AppIcon::UpdatesAvailable => (
+                &config.updates_available,
+                "software-update-available-symbolic",
+            ),
+            AppIcon::UpToDate => (&config.up_to_date, "emblem-default-symbolic"),
+            AppIcon::Loading => (&config.loading, "emblem-synchronizing-symbolic"),
+            AppIcon::Error => (&config.error, "dialog-error-symbolic"),
+            AppIcon::Stale => (&config.stale, "dialog-warning-symbolic"),
+            AppIcon::Paused => (&config.paused, "media-playback-pause-symbolic"),
+        };
+        icon_override.as_deref().unwrap_or(default)
     }
 }
 
@@ -49,15 +63,37 @@ pub fn view(app: &CosmicAppletArch) -> Element<Message> {
         return app
             .core
             .applet
-            .icon_button(icon.to_str())
+            .icon_button(icon.to_str(&app.config.icons))
             .on_press_down(Message::TogglePopup)
             .into();
     };
 
-    let total_updates = updates.pacman.len() + updates.aur.len() + updates.devel.len();
+    // Excluded packages are still shown in the popup, but not counted here.
+    let counted = |pkgname: &String| !app.config.exclude_matcher.is_match(pkgname);
+    let pm = updates
+        .pacman
+        .iter()
+        .filter(|u| counted(&u.pkgname))
+        .count();
+    let aur = updates.aur.iter().filter(|u| counted(&u.pkgname)).count();
+    let dev = updates.devel.iter().filter(|u| counted(&u.pkgname)).count();
+    let fp = updates
+        .flatpak
+        .iter()
+        .filter(|u| counted(&u.app_id))
+        .count();
+    let total_updates = pm + aur + dev + fp;
+    let excluded =
+        updates.pacman.len() + updates.aur.len() + updates.devel.len() + updates.flatpak.len()
+            - total_updates;
+    let indicate_excluded = app.config.indicate_excluded && excluded > 0;
 
     if app.error.is_none() {
-        if total_updates > 0 {
+        if app.online_checks_paused || app.is_user_paused() {
+            icon = AppIcon::Paused;
+        } else if minutes_since_stale_check(app).is_some() {
+            icon = AppIcon::Stale;
+        } else if total_updates > 0 {
             icon = AppIcon::UpdatesAvailable;
         } else {
             icon = AppIcon::UpToDate;
@@ -67,13 +103,35 @@ pub fn view(app: &CosmicAppletArch) -> Element<Message> {
     // TODO: Set a width when layout is vertical, button should be same width as
     // others.
     cosmic::widget::autosize::autosize(
-        if total_updates > 0 {
-            applet_button_with_text(app.core(), icon.to_str(), format!("{total_updates}"))
+        if (total_updates > 0 || indicate_excluded)
+            && !matches!(app.config.panel_count_style, PanelCountStyle::IconOnly)
+        {
+            let mut text = match app.config.panel_count_style {
+                // Per source counts don't fit in a vertical panel.
+                PanelCountStyle::PerSource if app.core.applet.is_horizontal() => {
+                    if app.config.check_flatpak {
+                        fl!(
+                            "panel-per-source-flatpak",
+                            pacman = pm,
+                            aur = aur,
+                            devel = dev,
+                            flatpak = fp
+                        )
+                    } else {
+                        fl!("panel-per-source", pacman = pm, aur = aur, devel = dev)
+                    }
+                }
+                _ => format!("{total_updates}"),
+            };
+            if indicate_excluded {
+                text.push('+');
+            }
+            applet_button_with_text(app.core(), icon.to_str(&app.config.icons), text)
                 .on_press_down(Message::TogglePopup)
         } else {
             app.core
                 .applet
-                .icon_button(icon.to_str())
+                .icon_button(icon.to_str(&app.config.icons))
                 .on_press_down(Message::TogglePopup)
         },
         AUTOSIZE_MAIN_ID.clone(),
@@ -91,13 +149,26 @@ pub fn view_window(app: &CosmicAppletArch, _id: cosmic::iced::window::Id) -> Ele
         .padding([space_xxs, 0]);
 
     let Some(updates) = app.updates.as_ref() else {
-        let content_list = content_list.push(body_text_row(fl!("loading")));
+        let content_list = match app.error.as_ref() {
+            // If the first check failed, show the error and allow a retry, rather than
+            // appearing to load forever.
+            Some(error) => content_list
+                .push(errors_row(error))
+                .push(cosmic_applet_divider(space_s).into())
+                .push(last_checked_row(app.last_checked)),
+            None => content_list.push(body_text_row(fl!("loading"))).push_maybe(
+                app.progress
+                    .as_ref()
+                    .map(|progress| body_text_row(progress.to_string())),
+            ),
+        };
         return app.core.applet.popup_container(content_list).into();
     };
 
     let pm = updates.pacman.len();
     let aur = updates.aur.len();
     let dev = updates.devel.len();
+    let fp = updates.flatpak.len();
 
     let pacman_list = collapsible_two_column_list(
         updates.pacman.iter().map(pretty_print_update),
@@ -105,21 +176,19 @@ pub fn view_window(app: &CosmicAppletArch, _id: cosmic::iced::window::Id) -> Ele
         fl!(
             "updates-available",
             numberUpdates = pm,
-            updateSource = "pacman"
+            updateSource = fl!("source-pacman")
         ),
         Message::ToggleCollapsible(crate::app::UpdateType::Pacman),
-        MAX_LINES,
     );
     let aur_list = collapsible_two_column_list(
-        updates.aur.iter().map(pretty_print_update),
+        updates.aur.iter().map(pretty_print_aur_update),
         &app.aur_list_state,
         fl!(
             "updates-available",
             numberUpdates = aur,
-            updateSource = "AUR"
+            updateSource = fl!("source-aur")
         ),
         Message::ToggleCollapsible(crate::app::UpdateType::Aur),
-        MAX_LINES,
     );
     let devel_list = collapsible_two_column_list(
         updates.devel.iter().map(pretty_print_devel_update),
@@ -127,37 +196,106 @@ pub fn view_window(app: &CosmicAppletArch, _id: cosmic::iced::window::Id) -> Ele
         fl!(
             "updates-available",
             numberUpdates = dev,
-            updateSource = "devel"
+            updateSource = fl!("source-devel")
         ),
         Message::ToggleCollapsible(crate::app::UpdateType::Devel),
-        MAX_LINES,
+    );
+    let flatpak_list = collapsible_two_column_list(
+        updates.flatpak.iter().map(pretty_print_flatpak_update),
+        &app.flatpak_list_state,
+        fl!(
+            "updates-available",
+            numberUpdates = fp,
+            updateSource = fl!("source-flatpak")
+        ),
+        Message::ToggleCollapsible(crate::app::UpdateType::Flatpak),
     );
 
-    let last_checked = match app.last_checked {
-        Some(t) => format!("{}", t.format("%x %-I:%M %p")),
-        None => fl!("not-yet"),
-    };
+    // Held back updates aren't counted in the total, as they won't be installed.
+    let held = updates.held_back.len();
+    let held_back_list = collapsible_two_column_list(
+        updates.held_back.iter().map(pretty_print_update),
+        &app.held_back_list_state,
+        fl!("held-back", numberUpdates = held),
+        Message::ToggleCollapsible(crate::app::UpdateType::HeldBack),
+    );
 
-    let total_updates = pm + aur + dev;
+    let total_updates = pm + aur + dev + fp;
     let content_list = content_list
+        .push_maybe(refreshing_row(app))
         .push_maybe((pm > 0).then_some(pacman_list))
         .push_maybe((aur > 0 && pm > 0).then_some(cosmic_applet_divider(space_s).into()))
         .push_maybe((aur > 0).then_some(aur_list))
         .push_maybe((dev > 0 && pm + aur > 0).then_some(cosmic_applet_divider(space_s).into()))
         .push_maybe((dev > 0).then_some(devel_list))
+        .push_maybe((fp > 0 && pm + aur + dev > 0).then_some(cosmic_applet_divider(space_s).into()))
+        .push_maybe((fp > 0).then_some(flatpak_list))
         .push_maybe((total_updates == 0).then_some(body_text_row(fl!("no-updates-available"))))
+        .push_maybe((held > 0).then_some(cosmic_applet_divider(space_s).into()))
+        .push_maybe((held > 0).then_some(held_back_list))
+        .push_maybe(orphaned_row(&updates.orphaned))
+        .push_maybe(
+            updates
+                .reboot_recommended
+                .then(|| body_text_row(fl!("reboot-recommended"))),
+        )
+        .push_maybe(warnings_column(&updates.warnings))
+        .push_maybe(
+            app.online_checks_paused
+                .then(|| body_text_row(fl!("online-checks-paused"))),
+        )
+        .push_maybe(
+            minutes_since_stale_check(app)
+                .map(|minutes| errors_row(fl!("stale", minutes = minutes))),
+        )
         .push(cosmic_applet_divider(space_s).into())
-        .push(
-            cosmic::applet::menu_button(cosmic::widget::text::body(fl!(
-                "last-checked",
-                dateTime = last_checked
-            )))
-            .on_press(Message::ForceGetUpdates),
+        .push(last_checked_row(app.last_checked))
+        .push(toggle_paused_row(app.is_user_paused()))
+        .push_maybe(
+            app.config
+                .sync_database_command
+                .is_some()
+                .then(sync_database_row),
         )
+        .push_maybe(
+            minutes_until_next_check(app)
+                .map(|minutes| body_text_row(fl!("next-check", minutes = minutes))),
+        )
+        .push_maybe(updates.pacman_db_synced.map(db_synced_row))
         .push_maybe(app.error.as_ref().map(errors_row));
     app.core.applet.popup_container(content_list).into()
 }
 
+/// If the last successful online check is older than the configured threshold,
+/// returns how many minutes ago it was.
+fn minutes_since_stale_check(app: &CosmicAppletArch) -> Option<i64> {
+    let age = Local::now() - app.last_checked?;
+    let stale_after = TimeDelta::seconds(app.config.stale_after_secs as i64);
+    (age > stale_after).then_some(age.num_minutes())
+}
+
+/// While an online check is running, shows that the displayed updates are
+/// being refreshed.
+fn refreshing_row(app: &CosmicAppletArch) -> Option<Element<'static, Message>> {
+    let text = match (&app.progress, app.refreshing) {
+        (Some(progress), _) => fl!("refreshing-progress", progress = progress.to_string()),
+        (None, true) => fl!("refreshing"),
+        (None, false) => return None,
+    };
+    Some(body_text_row(text))
+}
+
+/// How many minutes until the next scheduled online check (including retries
+/// of a failed check), rounded up. Returns None if checks are paused or none
+/// are scheduled yet.
+fn minutes_until_next_check(app: &CosmicAppletArch) -> Option<i64> {
+    if app.online_checks_paused || app.is_user_paused() {
+        return None;
+    }
+    let remaining = app.next_online_check? - Local::now();
+    (remaining > TimeDelta::zero()).then(|| (remaining.num_seconds() + 59) / 60)
+}
+
 fn cosmic_applet_divider(
     spacing: u16,
 ) -> impl Widget<Message, cosmic::Theme, cosmic::Renderer> + Into<Element<'static, Message>> {
@@ -192,6 +330,68 @@ fn body_text_row(text: String) -> Element<'static, Message> {
     .into()
 }
 
+/// Displays the last checked time, and forces an update check when pressed.
+fn last_checked_row(last_checked: Option<DateTime<Local>>) -> Element<'static, Message> {
+    let last_checked = match last_checked {
+        Some(t) => format!("{}", t.format("%x %-I:%M %p")),
+        None => fl!("not-yet"),
+    };
+    cosmic::applet::menu_button(cosmic::widget::text::body(fl!(
+        "last-checked",
+        dateTime = last_checked
+    )))
+    .on_press(Message::ForceGetUpdates)
+    .into()
+}
+
+fn toggle_paused_row(paused: bool) -> Element<'static, Message> {
+    let text = match paused {
+        true => fl!("resume-checks"),
+        false => fl!("pause-checks"),
+    };
+    cosmic::applet::menu_button(cosmic::widget::text::body(text))
+        .on_press(Message::TogglePaused)
+        .into()
+}
+
+fn sync_database_row() -> Element<'static, Message> {
+    cosmic::applet::menu_button(cosmic::widget::text::body(fl!("sync-database")))
+        .on_press(Message::SyncDatabase)
+        .into()
+}
+
+/// Displays when the pacman sync database was last synced, which may differ
+/// from the last checked time as offline checks don't sync it.
+fn db_synced_row(db_synced: DateTime<Local>) -> Element<'static, Message> {
+    body_text_row(fl!(
+        "db-last-synced",
+        dateTime = format!("{}", db_synced.format("%x %-I:%M %p"))
+    ))
+}
+
+/// Warns about installed AUR packages that are no longer on the AUR, if any.
+fn orphaned_row(orphaned: &[Package]) -> Option<Element<'static, Message>> {
+    if orphaned.is_empty() {
+        return None;
+    }
+    let packages = orphaned
+        .iter()
+        .map(|package| package.pkgname.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(errors_row(fl!("not-in-aur", packages = packages)))
+}
+
+fn warnings_column(warnings: &[String]) -> Option<Element<'static, Message>> {
+    if warnings.is_empty() {
+        return None;
+    }
+    Some(
+        cosmic::widget::column::with_children(warnings.iter().map(errors_row).collect::<Vec<_>>())
+            .into(),
+    )
+}
+
 fn errors_row(error: impl Display) -> Element<'static, Message> {
     cosmic::widget::container(
         cosmic::widget::text::body(format!("Warning: {error}!!"))
@@ -204,11 +404,10 @@ fn errors_row(error: impl Display) -> Element<'static, Message> {
 }
 
 fn collapsible_two_column_list<'a>(
-    text: impl ExactSizeIterator<Item = (String, String)> + 'a,
+    text: impl Iterator<Item = (String, String)> + 'a,
     collapsed: &Collapsed,
     title: String,
     on_press_mesage: Message,
-    max_items: usize,
 ) -> Element<'a, Message> {
     let cosmic::cosmic_theme::Spacing { space_xxs, .. } = theme::active().cosmic().spacing;
 
@@ -217,16 +416,6 @@ fn collapsible_two_column_list<'a>(
         Collapsed::Expanded => "go-up-symbolic",
     };
 
-    let list_len = text.len();
-
-    let overflow_line = {
-        if list_len > max_items {
-            Some((fl!("n-more", n = (list_len - max_items)), "".to_string()))
-        } else {
-            None
-        }
-    };
-
     let heading = cosmic::applet::menu_button(cosmic::iced_widget::row![
         cosmic::widget::text::body(title)
             .width(Length::Fill)
@@ -246,8 +435,12 @@ fn collapsible_two_column_list<'a>(
     match collapsed {
         Collapsed::Collapsed => heading.into(),
         Collapsed::Expanded => {
-            let children =
-                two_column_text_widget(text.take(max_items).chain(overflow_line), space_xxs);
+            // Long lists scroll, so that every update can be seen without the popup
+            // exceeding its maximum height.
+            let children = cosmic::widget::container(cosmic::widget::scrollable(
+                two_column_text_widget(text, space_xxs),
+            ))
+            .max_height(MAX_LIST_HEIGHT);
             cosmic::iced_widget::column![heading, children].into()
         }
     }
@@ -273,16 +466,73 @@ fn two_column_text_widget<'a>(
 }
 
 /// (name, upgrade)
+/// Packages in HoldPkg are marked, as updates to these warrant attention.
 fn pretty_print_update(update: &Update) -> (String, String) {
+    let hold = match update.hold {
+        true => " ❗",
+        false => "",
+    };
     (
-        update.pkgname.to_string(),
-        format!(
-            "{}-{}->{}-{}",
-            update.pkgver_cur, update.pkgrel_cur, update.pkgver_new, update.pkgrel_new
+        format!("{}{hold}", update.pkgname),
+        pretty_print_version_change(
+            &update.pkgver_cur,
+            &update.pkgrel_cur,
+            &update.pkgver_new,
+            &update.pkgrel_new,
         ),
     )
 }
 
+/// (name, upgrade)
+/// Packages flagged out-of-date on the AUR are marked with a warning glyph.
+fn pretty_print_aur_update(update: &AurUpdate) -> (String, String) {
+    let flag = match update.flagged_out_of_date {
+        Some(_) => " ⚠",
+        None => "",
+    };
+    (
+        format!("{}{flag}", update.pkgname),
+        pretty_print_version_change(
+            &update.pkgver_cur,
+            &update.pkgrel_cur,
+            &update.pkgver_new,
+            &update.pkgrel_new,
+        ),
+    )
+}
+
+/// Formats a version change, e.g `1.0-1->*1.1*-1`. The first component of the
+/// new version that differs (epoch, pkgver or pkgrel) is wrapped in `*`, so
+/// that e.g an epoch bump in `1:2.0-1->*2*:1.0-1` isn't missed.
+fn pretty_print_version_change(
+    pkgver_cur: &str,
+    pkgrel_cur: &str,
+    pkgver_new: &str,
+    pkgrel_new: &str,
+) -> String {
+    // Packages without an epoch have an implicit epoch of 0.
+    let split_epoch = |pkgver: &str| match pkgver.split_once(':') {
+        Some((epoch, pkgver)) => (epoch.to_string(), pkgver.to_string()),
+        None => ("0".to_string(), pkgver.to_string()),
+    };
+    let (epoch_cur, ver_cur) = split_epoch(pkgver_cur);
+    let (epoch_new, ver_new) = split_epoch(pkgver_new);
+    let epoch_prefix = match pkgver_new.contains(':') {
+        true => format!("{epoch_new}:"),
+        false => String::new(),
+    };
+    let new = if epoch_cur != epoch_new {
+        format!("*{epoch_new}*:{ver_new}-{pkgrel_new}")
+    } else if ver_cur != ver_new {
+        format!("{epoch_prefix}*{ver_new}*-{pkgrel_new}")
+    } else if pkgrel_cur != pkgrel_new {
+        format!("{epoch_prefix}{ver_new}-*{pkgrel_new}*")
+    } else {
+        format!("{pkgver_new}-{pkgrel_new}")
+    };
+    format!("{pkgver_cur}-{pkgrel_cur}->{new}")
+}
+
 /// (name, upgrade)
 fn pretty_print_devel_update(update: &DevelUpdate) -> (String, String) {
     (
@@ -294,6 +544,14 @@ fn pretty_print_devel_update(update: &DevelUpdate) -> (String, String) {
     )
 }
 
+/// (app id, remote/branch)
+fn pretty_print_flatpak_update(update: &FlatpakUpdate) -> (String, String) {
+    (
+        update.app_id.to_string(),
+        format!("{}/{}", update.remote, update.branch),
+    )
+}
+
 // Extension of applet context icon_button_from_handle function.
 pub fn applet_button_with_text<'a, Message: 'static>(
     core: &Core,
@@ -341,3 +599,55 @@ pub fn applet_button_with_text<'a, Message: 'static>(
     .align_y(Vertical::Center.into());
     cosmic::widget::button::custom(container).class(Button::AppletIcon)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::pretty_print_version_change;
+
+    #[test]
+    fn test_pretty_print_epoch_change() {
+        assert_eq!(
+            pretty_print_version_change("2.0", "1", "1:1.0", "1"),
+            "2.0-1->*1*:1.0-1"
+        );
+        assert_eq!(
+            pretty_print_version_change("1:2.0", "1", "2:1.0", "1"),
+            "1:2.0-1->*2*:1.0-1"
+        );
+    }
+    #[test]
+    fn test_pretty_print_pkgver_change() {
+        assert_eq!(
+            pretty_print_version_change("1.0", "1", "1.1", "1"),
+            "1.0-1->*1.1*-1"
+        );
+        assert_eq!(
+            pretty_print_version_change("1:1.0", "2", "1:1.1", "1"),
+            "1:1.0-2->1:*1.1*-1"
+        );
+    }
+    #[test]
+    fn test_pretty_print_pkgrel_only_change() {
+        assert_eq!(
+            pretty_print_version_change("1.0", "1", "1.0", "2"),
+            "1.0-1->1.0-*2*"
+        );
+        assert_eq!(
+            pretty_print_version_change("1:1.0", "1", "1:1.0", "2"),
+            "1:1.0-1->1:1.0-*2*"
+        );
+    }
+    #[test]
+    fn test_pretty_print_unparsable_version() {
+        // Versions that don't follow the usual format are still shown, compared
+        // as plain strings.
+        assert_eq!(
+            pretty_print_version_change("not-a-version", "", "also:not:a-version", ""),
+            "not-a-version-->*also*:not:a-version-"
+        );
+        assert_eq!(
+            pretty_print_version_change("git", "1", "git", "1"),
+            "git-1->git-1"
+        );
+    }
+}
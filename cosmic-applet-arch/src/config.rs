@@ -0,0 +1,272 @@
+//! User configuration for the applet, read from
+//! `$XDG_CONFIG_HOME/cosmic-applet-arch/config.ron` at startup. All fields are
+//! optional, and missing fields use their default values.
+//!
+//! Example config file:
+//!```ron
+//! (
+//!     icons: (
+//!         updates_available: Some("system-software-update-symbolic"),
+//!     ),
+//! )
+//! ```
+use crate::app::UpdateType;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::{collections::HashSet, path::PathBuf};
+
+const CONFIG_DIR_NAME: &str = "cosmic-applet-arch";
+const CONFIG_FILE_NAME: &str = "config.ron";
+/// Minimum height of the popup, which `popup_max_height` can't be set below.
+pub const MIN_POPUP_HEIGHT: u32 = 200;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub icons: IconConfig,
+    /// If the last successful online check is older than this, the panel icon
+    /// shows that the displayed updates may be out of date.
+    pub stale_after_secs: u64,
+    /// Skip scheduled online checks while running on battery. Offline checks
+    /// continue, and a manual refresh still runs an online check.
+    pub pause_on_battery: bool,
+    /// Proxy URL used for all HTTP requests. If not set, the `HTTP_PROXY` and
+    /// `HTTPS_PROXY` environment variables are used.
+    pub proxy: Option<String>,
+    /// Timeout for each HTTP request. The whole check also has its own timeout.
+    /// 0 isn't a valid timeout, so the default is used instead.
+    pub http_timeout_secs: u64,
+    /// Update lists that are expanded when the popup is first opened. After
+    /// that, lists stay expanded or collapsed between popup opens. Lists with
+    /// no updates are hidden regardless.
+    pub default_expanded: HashSet<UpdateType>,
+    /// How update counts are shown next to the panel icon.
+    pub panel_count_style: PanelCountStyle,
+    /// Packages that aren't counted in the panel, as glob patterns (e.g
+    /// `*-git`). They are still shown in the popup.
+    pub exclude_packages: HashSet<String>,
+    /// Add a `+` to the panel count when updates to excluded packages are
+    /// available, e.g `4+`.
+    pub indicate_excluded: bool,
+    /// `exclude_packages` compiled when the config is loaded.
+    #[serde(skip)]
+    pub exclude_matcher: GlobSet,
+    /// Don't show pacman and AUR updates where only the pkgrel has changed
+    /// (rebuilds without a new upstream version).
+    pub ignore_pkgrel_only: bool,
+    /// Also check for flatpak updates. Requires `flatpak` to be installed.
+    pub check_flatpak: bool,
+    /// Command to sync the pacman database, e.g `["pkexec", "pacman", "-Sy"]`.
+    /// If set, a button to run it is shown in the popup. Installing packages
+    /// after syncing without upgrading the whole system is a partial upgrade,
+    /// which is unsupported and can break the system.
+    pub sync_database_command: Option<Vec<String>>,
+    /// Size limits of the popup, in logical pixels.
+    pub popup_min_width: u32,
+    pub popup_max_width: u32,
+    pub popup_max_height: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            icons: Default::default(),
+            stale_after_secs: 60 * 60 * 3,
+            pause_on_battery: false,
+            proxy: None,
+            http_timeout_secs: 15,
+            default_expanded: HashSet::new(),
+            panel_count_style: PanelCountStyle::default(),
+            exclude_packages: HashSet::new(),
+            indicate_excluded: false,
+            exclude_matcher: GlobSet::empty(),
+            ignore_pkgrel_only: false,
+            check_flatpak: false,
+            sync_database_command: None,
+            popup_min_width: 300,
+            popup_max_width: 444,
+            popup_max_height: 1080,
+        }
+    }
+}
+
+impl Config {
+    /// Reset the popup size limits to their defaults if they can't be used.
+    fn validate_popup_size(&mut self) {
+        let Config {
+            popup_min_width,
+            popup_max_width,
+            popup_max_height,
+            ..
+        } = *self;
+        if popup_min_width == 0
+            || popup_max_height < MIN_POPUP_HEIGHT
+            || popup_min_width > popup_max_width
+        {
+            eprintln!(
+                "Invalid popup size limits in config (min width {popup_min_width}, max width \
+                 {popup_max_width}, max height {popup_max_height}), using defaults"
+            );
+            let default = Config::default();
+            self.popup_min_width = default.popup_min_width;
+            self.popup_max_width = default.popup_max_width;
+            self.popup_max_height = default.popup_max_height;
+        }
+    }
+    /// Reset the HTTP timeout to its default if it's 0, as every request would
+    /// time out immediately.
+    fn validate_http_timeout(&mut self) {
+        if self.http_timeout_secs == 0 {
+            eprintln!("Invalid HTTP timeout in config (0 seconds), using default");
+            self.http_timeout_secs = Config::default().http_timeout_secs;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub enum PanelCountStyle {
+    /// Total number of updates, e.g `4`.
+    #[default]
+    Total,
+    /// Number of updates from each source, e.g `P:3 A:1 D:0`. Vertical panels
+    /// are too narrow for this, so show the total instead.
+    PerSource,
+    /// No count, only the status icon. The popup still lists all updates.
+    IconOnly,
+}
+
+/// Overrides for the panel icon names. `None` uses the default icon.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct IconConfig {
+    pub loading: Option<String>,
+    pub error: Option<String>,
+    pub updates_available: Option<String>,
+    pub up_to_date: Option<String>,
+    pub stale: Option<String>,
+    pub paused: Option<String>,
+}
+
+fn config_path() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join(CONFIG_DIR_NAME)
+            .join(CONFIG_FILE_NAME),
+    )
+}
+
+/// Load the config file. If the file doesn't exist, or can't be read, the
+/// default config is used.
+pub fn load_config() -> Config {
+    let Some(path) = config_path() else {
+        eprintln!("Unable to determine config directory, using default config");
+        return Config::default();
+    };
+    let file = match std::fs::read_to_string(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Config::default(),
+        Err(e) => {
+            eprintln!("Error {e} reading config file {path:?}, using default config");
+            return Config::default();
+        }
+    };
+    let mut config: Config = ron::from_str(&file).unwrap_or_else(|e| {
+        eprintln!("Error {e} parsing config file {path:?}, using default config");
+        Config::default()
+    });
+    config.exclude_matcher = build_glob_set(&config.exclude_packages);
+    config.validate_popup_size();
+    config.validate_http_timeout();
+    config
+}
+
+/// Compile a set of glob patterns. Invalid patterns are skipped with a warning,
+/// rather than discarding the whole config.
+fn build_glob_set(patterns: &HashSet<String>) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => eprintln!("Error {e} in config, ignoring pattern `{pattern}`"),
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        eprintln!("Error {e} building patterns from config, ignoring them");
+        GlobSet::empty()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_glob_set, Config, MIN_POPUP_HEIGHT};
+    use std::collections::HashSet;
+
+    fn popup_size(config: &Config) -> (u32, u32, u32) {
+        (
+            config.popup_min_width,
+            config.popup_max_width,
+            config.popup_max_height,
+        )
+    }
+    fn validated_popup_size(min_width: u32, max_width: u32, max_height: u32) -> (u32, u32, u32) {
+        let mut config = Config {
+            popup_min_width: min_width,
+            popup_max_width: max_width,
+            popup_max_height: max_height,
+            ..Default::default()
+        };
+        config.validate_popup_size();
+        popup_size(&config)
+    }
+    #[test]
+    fn test_validate_popup_size() {
+        assert_eq!(validated_popup_size(200, 600, 800), (200, 600, 800));
+        assert_eq!(
+            validated_popup_size(400, 400, MIN_POPUP_HEIGHT),
+            (400, 400, MIN_POPUP_HEIGHT)
+        );
+    }
+    #[test]
+    fn test_validate_popup_size_zero_min_width() {
+        let default = popup_size(&Config::default());
+        assert_eq!(validated_popup_size(0, 600, 800), default);
+    }
+    #[test]
+    fn test_validate_popup_size_min_above_max() {
+        let default = popup_size(&Config::default());
+        assert_eq!(validated_popup_size(601, 600, 800), default);
+    }
+    #[test]
+    fn test_validate_popup_size_max_height_too_small() {
+        let default = popup_size(&Config::default());
+        assert_eq!(
+            validated_popup_size(200, 600, MIN_POPUP_HEIGHT - 1),
+            default
+        );
+    }
+    #[test]
+    fn test_build_glob_set_skips_invalid() {
+        let patterns = HashSet::from(["*-git".to_string(), "[".to_string()]);
+        let set = build_glob_set(&patterns);
+        assert_eq!(set.len(), 1);
+        assert!(set.is_match("paru-git"));
+        assert!(!set.is_match("paru"));
+    }
+    #[test]
+    fn test_validate_http_timeout() {
+        let mut config = Config {
+            http_timeout_secs: 30,
+            ..Default::default()
+        };
+        config.validate_http_timeout();
+        assert_eq!(config.http_timeout_secs, 30);
+        config.http_timeout_secs = 0;
+        config.validate_http_timeout();
+        assert_eq!(
+            config.http_timeout_secs,
+            Config::default().http_timeout_secs
+        );
+    }
+}
@@ -3,10 +3,11 @@
 /// The `app` module is used by convention to indicate the main component of our
 /// application.
 mod app;
+mod config;
 mod core;
 
 use app::CosmicAppletArch;
 
 fn main() -> cosmic::iced::Result {
-    cosmic::applet::run::<CosmicAppletArch>(())
+    cosmic::applet::run::<CosmicAppletArch>(config::load_config())
 }